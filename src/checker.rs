@@ -0,0 +1,71 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::temp;
+
+/// A project's own program for judging outputs, declared as `checker` in its `.cvutie`.
+pub struct Checker {
+    /// Shell command, run in `dir` with the input, expected and actual output paths appended.
+    pub command: String,
+    pub dir: PathBuf,
+}
+
+/// What a checker decided about one output.
+pub struct Decision {
+    pub accepted: bool,
+    /// The checker's stdout, or its stderr if it printed nothing else.
+    pub message: String,
+}
+
+impl Checker {
+    /// Runs the checker on `actual`. Exit code 0 accepts and 1 rejects, anything else means the
+    /// checker itself failed.
+    pub fn check(&self, input: &Path, expected: &Path, actual: &[u8]) -> Result<Decision> {
+        let (actual_path, mut file) = temp::file("actual", ".txt")?;
+        file.write_all(actual)
+            .with_context(|| format!("Could not write `{}`", actual_path.display()))?;
+        drop(file);
+
+        // The checker runs in the project directory, so relative case paths would break.
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", self.command))
+            .arg(&self.command)
+            .arg(std::path::absolute(input)?)
+            .arg(std::path::absolute(expected)?)
+            .arg(&actual_path)
+            .current_dir(&self.dir)
+            .output();
+        let _ = std::fs::remove_file(&actual_path);
+        let output = output.with_context(|| format!("Failed to run checker `{}`", self.command))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let message = if stdout.is_empty() {
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        } else {
+            stdout
+        };
+        match output.status.code() {
+            Some(0) => Ok(Decision {
+                accepted: true,
+                message,
+            }),
+            Some(1) => Ok(Decision {
+                accepted: false,
+                message,
+            }),
+            _ if message.is_empty() => {
+                bail!("Checker `{}` failed ({})", self.command, output.status)
+            }
+            _ => bail!(
+                "Checker `{}` failed ({}): {message}",
+                self.command,
+                output.status
+            ),
+        }
+    }
+}
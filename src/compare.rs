@@ -0,0 +1,123 @@
+use crate::config::Comparison;
+
+/// Lines without trailing whitespace, dropping blank lines at the end.
+fn trimmed_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn numbers_match(expected: &str, actual: &str, abs_epsilon: f64, rel_epsilon: f64) -> bool {
+    if expected == actual {
+        return true;
+    }
+    let (Ok(e), Ok(a)) = (expected.parse::<f64>(), actual.parse::<f64>()) else {
+        return false;
+    };
+    let difference = (e - a).abs();
+    difference <= abs_epsilon || difference <= rel_epsilon * e.abs()
+}
+
+/// Whether `actual` is an acceptable output for `expected` under `comparison`.
+pub fn matches(comparison: &Comparison, expected: &[u8], actual: &[u8]) -> bool {
+    if expected == actual {
+        return true;
+    }
+    let (expected, actual) = (
+        String::from_utf8_lossy(expected),
+        String::from_utf8_lossy(actual),
+    );
+    match *comparison {
+        Comparison::Exact => false,
+        Comparison::IgnoreTrailingWhitespace => trimmed_lines(&expected) == trimmed_lines(&actual),
+        Comparison::IgnoreWhitespace => expected.split_whitespace().eq(actual.split_whitespace()),
+        Comparison::UnorderedLines => {
+            let (mut expected, mut actual) = (trimmed_lines(&expected), trimmed_lines(&actual));
+            expected.sort_unstable();
+            actual.sort_unstable();
+            expected == actual
+        }
+        Comparison::Numeric {
+            abs_epsilon,
+            rel_epsilon,
+        } => {
+            let (expected, actual): (Vec<_>, Vec<_>) = (
+                expected.split_whitespace().collect(),
+                actual.split_whitespace().collect(),
+            );
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(&actual)
+                    .all(|(e, a)| numbers_match(e, a, abs_epsilon, rel_epsilon))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(abs_epsilon: f64, rel_epsilon: f64) -> Comparison {
+        Comparison::Numeric {
+            abs_epsilon,
+            rel_epsilon,
+        }
+    }
+
+    #[test]
+    fn exact_compares_bytes() {
+        assert!(matches(&Comparison::Exact, b"1 2\n", b"1 2\n"));
+        assert!(!matches(&Comparison::Exact, b"1 2\n", b"1 2 \n"));
+        assert!(!matches(&Comparison::Exact, b"1 2\n", b"1 2\r\n"));
+    }
+
+    #[test]
+    fn trailing_whitespace_and_blank_lines_are_ignored() {
+        let comparison = Comparison::IgnoreTrailingWhitespace;
+        assert!(matches(&comparison, b"a b\nc\n", b"a b  \nc\t\n\n\n"));
+        assert!(matches(&comparison, b"a b\nc\n", b"a b\r\nc"));
+        assert!(!matches(&comparison, b"a b\nc\n", b"a  b\nc\n"));
+        assert!(!matches(&comparison, b"a\n\nb\n", b"a\nb\n"));
+    }
+
+    #[test]
+    fn whitespace_only_separates_tokens() {
+        let comparison = Comparison::IgnoreWhitespace;
+        assert!(matches(&comparison, b"1 2\n3\n", b"1\n2   3"));
+        assert!(!matches(&comparison, b"1 2 3\n", b"1 23\n"));
+    }
+
+    #[test]
+    fn unordered_lines_may_come_in_any_order() {
+        let comparison = Comparison::UnorderedLines;
+        assert!(matches(&comparison, b"b\na\nc\n", b"a \nc\nb\n\n"));
+        assert!(!matches(&comparison, b"a\na\nb\n", b"a\nb\nb\n"));
+    }
+
+    #[test]
+    fn numbers_within_absolute_epsilon_match() {
+        let comparison = numeric(1e-6, 0.0);
+        assert!(matches(&comparison, b"0.333333\n", b"0.3333333333\n"));
+        assert!(matches(&comparison, b"1.0 2.0", b"1.0000005\n2"));
+        assert!(!matches(&comparison, b"0.333333\n", b"0.3334\n"));
+    }
+
+    #[test]
+    fn numbers_within_relative_epsilon_match() {
+        let comparison = numeric(0.0, 1e-3);
+        assert!(matches(&comparison, b"1000000\n", b"1000500\n"));
+        assert!(!matches(&comparison, b"1000000\n", b"1002000\n"));
+        assert!(!matches(&comparison, b"0\n", b"0.0001\n"));
+    }
+
+    #[test]
+    fn numeric_needs_equal_words_and_token_counts() {
+        let comparison = numeric(0.1, 0.1);
+        assert!(matches(&comparison, b"YES 1.5\n", b"YES 1.55\n"));
+        assert!(!matches(&comparison, b"YES 1.5\n", b"yes 1.5\n"));
+        assert!(!matches(&comparison, b"1 2\n", b"1 2 3\n"));
+    }
+}
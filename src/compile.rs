@@ -0,0 +1,82 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::config::Config;
+
+/// The compiler and arguments a build uses.
+pub struct Toolchain {
+    pub compiler: String,
+    pub args: Vec<String>,
+}
+
+impl Toolchain {
+    /// Uses the named profile from `Config::profiles`, or `c_compiler` with `c_compiler_opts`.
+    pub fn resolve(config: &Config, profile: Option<&str>) -> Result<Self> {
+        let Some(name) = profile else {
+            return Ok(Self {
+                compiler: config.c_compiler.clone(),
+                args: config.c_compiler_opts.clone(),
+            });
+        };
+
+        let Some(profile) = config.profiles.get(name) else {
+            let mut known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            bail!(
+                "Unknown profile `{name}`. Known profiles: {}",
+                known.join(", ")
+            );
+        };
+        Ok(Self {
+            compiler: profile.compiler.clone(),
+            args: profile.args(),
+        })
+    }
+}
+
+/// Compiles the project in `dir` with `toolchain` and returns the path of the produced binary.
+///
+/// `output` is resolved relative to `dir`, falling back to `Config::default_bin_output_name`.
+pub fn compile(
+    config: &Config,
+    toolchain: &Toolchain,
+    dir: &Path,
+    output: Option<&str>,
+) -> Result<PathBuf> {
+    let sources = find_sources(config, dir)?;
+    let binary = dir.join(output.unwrap_or(&config.default_bin_output_name));
+
+    let status = Command::new(&toolchain.compiler)
+        .args(&toolchain.args)
+        .arg("-o")
+        .arg(&binary)
+        .args(&sources)
+        .status()
+        .with_context(|| format!("Failed to run compiler `{}`", toolchain.compiler))?;
+
+    if !status.success() {
+        bail!("Compilation of `{}` failed ({status})", dir.display());
+    }
+    Ok(binary)
+}
+
+fn find_sources(config: &Config, dir: &Path) -> Result<Vec<PathBuf>> {
+    let sources: Vec<PathBuf> = config
+        .source_code_filenames
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect();
+
+    if sources.is_empty() {
+        bail!(
+            "No source files ({}) found in `{}`",
+            config.source_code_filenames.join(", "),
+            dir.display()
+        );
+    }
+    Ok(sources)
+}
@@ -1,14 +1,60 @@
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::{Map, Value};
 use std::{
     collections::HashMap,
+    fmt,
+    io::{self, Write},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+/// File name of both the home config and project-local configs.
+pub const CONFIG_FILE: &str = ".cvutie";
+/// Version written to new configs. Bump it together with a new entry in [`MIGRATIONS`].
+pub const CONFIG_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+    // Version 1 introduced `version` itself; missing fields are filled in from defaults.
+    |_| {},
+    // Version 2 fixed the default compiler flags, which produced an object file.
+    |map| {
+        let is_old_default = map
+            .get("c_compiler_opts")
+            .and_then(Value::as_array)
+            .is_some_and(|opts| {
+                opts.iter()
+                    .map(Value::as_str)
+                    .eq(C_COMPILER_OPTS_V1.iter().map(|&o| Some(o)))
+            });
+        if !is_old_default {
+            return;
+        }
+        map.insert(
+            "c_compiler_opts".to_string(),
+            C_COMPILER_OPTS_DEFAULT.into(),
+        );
+        // Only the old default compiler is swapped; one the user chose stays.
+        if map.get("c_compiler").and_then(Value::as_str) == Some(C_COMPILER_V1) {
+            map.insert("c_compiler".to_string(), C_COMPILER_DEFAULT.into());
+        }
+    },
+];
+
 const SOURCE_CODE_FILENAMES_DEFAULT: &str = "main.c";
 const DEFAULT_BINARY_OUTPUT_NAME_DEFAULT: &str = "out";
 const TEST_FOLDER_NAMES_DEFAULT: &[&str] = &["CZE", "ENG"];
-const C_COMPILER_DEFAULT: &str = "g++";
-const C_COMPILER_OPTS_DEFAULT: &[&str] = &[
+const C_COMPILER_DEFAULT: &str = "gcc";
+const C_STANDARD_DEFAULT: &str = "c11";
+const C_COMPILER_OPTS_DEFAULT: &[&str] =
+    &["-std=c11", "-Wall", "-pedantic", "-Wno-long-long", "-O2"];
+/// Default compiler of version 1 configs, replaced by the migration to version 2.
+const C_COMPILER_V1: &str = "g++";
+/// Default flags of version 1 configs, replaced by the migration to version 2.
+const C_COMPILER_OPTS_V1: &[&str] = &[
     "std=c++20",
     "-Wall",
     "-pedantic",
@@ -17,21 +63,119 @@ const C_COMPILER_OPTS_DEFAULT: &[&str] = &[
     "-c",
     "-o",
 ];
+const PROFILES_DEFAULT: &[(&str, &[&str])] = &[
+    ("progtest", &["-Wall", "-pedantic", "-Wno-long-long", "-O2"]),
+    ("debug", &["-Wall", "-Wextra", "-pedantic", "-g", "-O0"]),
+    (
+        "sanitize",
+        &[
+            "-Wall",
+            "-pedantic",
+            "-g",
+            "-fsanitize=address,undefined",
+            "-fno-omit-frame-pointer",
+        ],
+    ),
+    ("release", &["-O3", "-DNDEBUG"]),
+];
+
+/// A named set of compiler settings, selected with `compile --profile <name>`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub compiler: String,
+    /// Language standard passed as `-std=<std>`.
+    pub std: Option<String>,
+    pub flags: Vec<String>,
+}
+
+impl Profile {
+    /// The full list of arguments for the compiler, excluding sources and output.
+    pub fn args(&self) -> Vec<String> {
+        self.std
+            .iter()
+            .map(|std| format!("-std={std}"))
+            .chain(self.flags.iter().cloned())
+            .collect()
+    }
+}
+
+/// Shell commands run in the project directory around `compile` and `test-all`. A failing
+/// `pre_*` hook aborts the command.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    pub pre_compile: Option<String>,
+    /// Runs after a successful build, with the binary in `$CVUTIE_BINARY`.
+    pub post_compile: Option<String>,
+    pub pre_test: Option<String>,
+    /// Runs once the results are in, with the counts in `$CVUTIE_PASSED` and `$CVUTIE_FAILED`.
+    pub post_test: Option<String>,
+}
+
+/// How `test-all` compares a program's output with the expected output.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    /// Byte for byte.
+    #[default]
+    Exact,
+    /// Ignores whitespace at the end of lines and blank lines at the end of the output.
+    IgnoreTrailingWhitespace,
+    /// Compares whitespace-separated tokens, ignoring how they are separated.
+    IgnoreWhitespace,
+    /// Like `ignore_trailing_whitespace`, but the lines may come in any order.
+    UnorderedLines,
+    /// Compares tokens, accepting numbers within `abs_epsilon` or `rel_epsilon` of the expected
+    /// value.
+    Numeric {
+        #[serde(default)]
+        abs_epsilon: f64,
+        #[serde(default)]
+        rel_epsilon: f64,
+    },
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct Config {
+    /// Configs written before versioning was introduced have no `version` and count as 0.
+    #[serde(default)]
+    pub version: u32,
     pub c_compiler: String,
     pub c_compiler_opts: Vec<String>,
     pub source_code_filenames: Vec<String>,
     pub test_folder_names: Vec<String>,
     pub default_bin_output_name: String,
+    pub profiles: HashMap<String, Profile>,
     pub pipes: Option<HashMap<String, Vec<String>>>,
     pub regions: Option<HashMap<String, Vec<PathBuf>>>,
+    /// Wall-clock limit for a single run, in seconds.
+    #[serde(deserialize_with = "positive_secs")]
+    pub time_limit_secs: Option<f64>,
+    /// CPU time limit (`RLIMIT_CPU`) for a single run, in seconds.
+    #[serde(deserialize_with = "positive")]
+    pub cpu_time_limit_secs: Option<u64>,
+    /// Address space limit (`RLIMIT_AS`) for a single run, in megabytes.
+    #[serde(deserialize_with = "positive")]
+    pub memory_limit_mb: Option<u64>,
+    /// How outputs are compared, unless a region or the project's own `.cvutie` says otherwise.
+    pub comparison: Comparison,
+    /// Comparison per region name, overriding `comparison` for the projects in that region.
+    pub region_comparisons: Option<HashMap<String, Comparison>>,
+    /// Command that judges outputs in place of `comparison`, usually set in a project's own
+    /// `.cvutie`. It runs in the project directory as `<checker> <input> <expected> <actual>`
+    /// and accepts with exit code 0 or rejects with 1, explaining itself on stdout.
+    pub checker: Option<String>,
+    pub hooks: Hooks,
+    /// Hooks per region name, run after the global `hooks` for the projects in that region.
+    pub region_hooks: Option<HashMap<String, Hooks>>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             c_compiler: C_COMPILER_DEFAULT.to_string(),
             c_compiler_opts: C_COMPILER_OPTS_DEFAULT
                 .iter()
@@ -43,22 +187,433 @@ impl Default for Config {
                 .map(|&s| s.to_string())
                 .collect(),
             default_bin_output_name: DEFAULT_BINARY_OUTPUT_NAME_DEFAULT.to_string(),
+            profiles: PROFILES_DEFAULT
+                .iter()
+                .map(|&(name, flags)| {
+                    let profile = Profile {
+                        compiler: C_COMPILER_DEFAULT.to_string(),
+                        std: Some(C_STANDARD_DEFAULT.to_string()),
+                        flags: flags.iter().map(|&s| s.to_string()).collect(),
+                    };
+                    (name.to_string(), profile)
+                })
+                .collect(),
             pipes: None,
             regions: None,
+            time_limit_secs: None,
+            cpu_time_limit_secs: None,
+            memory_limit_mb: None,
+            comparison: Comparison::Exact,
+            region_comparisons: None,
+            checker: None,
+            hooks: Hooks::default(),
+            region_hooks: None,
         }
     }
 }
 
+/// Checks that a time limit is a positive number of seconds that fits a `Duration`.
+pub fn check_secs(secs: f64) -> Result<f64, String> {
+    if secs > 0.0 && Duration::try_from_secs_f64(secs).is_ok() {
+        Ok(secs)
+    } else {
+        Err(format!("expected a positive number of seconds, got {secs}"))
+    }
+}
+
+fn positive_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(check_secs)
+        .transpose()
+        .map_err(D::Error::custom)
+}
+
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    match Option::<u64>::deserialize(deserializer)? {
+        Some(0) => Err(D::Error::custom("expected a positive limit, got 0")),
+        limit => Ok(limit),
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Reading or writing the file failed.
+    Io { path: PathBuf, source: io::Error },
+    /// The file exists but isn't a valid config.
+    Invalid {
+        path: PathBuf,
+        /// 1-based line and column of the problem, when it can be pinned down.
+        location: Option<(usize, usize)>,
+        key: Option<String>,
+        message: String,
+    },
+    /// The config couldn't be serialized.
+    Serialize(serde_json::Error),
+}
+
+impl ConfigError {
+    /// True when the error only means the file doesn't exist yet.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::Io { source, .. } if source.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "`{}`: {source}", path.display()),
+            Self::Invalid {
+                path,
+                location,
+                key,
+                message,
+            } => {
+                write!(f, "`{}", path.display())?;
+                if let Some((line, column)) = location {
+                    write!(f, ":{line}:{column}")?;
+                }
+                write!(f, "` is not a valid config: ")?;
+                if let Some(key) = key {
+                    write!(f, "key `{key}`: ")?;
+                }
+                write!(f, "{message}")
+            }
+            Self::Serialize(source) => write!(f, "Failed to serialize config: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Serialize(source) => Some(source),
+            Self::Invalid { .. } => None,
+        }
+    }
+}
+
+/// Returns the 1-based line and column of the top-level `key` in `text`.
+fn locate_key(text: &str, key: &str) -> Option<(usize, usize)> {
+    let quoted = format!("\"{key}\"");
+    let offset = text
+        .match_indices(&quoted)
+        .map(|(i, _)| i)
+        .find(|&i| text[i + quoted.len()..].trim_start().starts_with(':'))?;
+
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    Some((line, column))
+}
+
+/// Parses `text` into `T`, rejecting keys `T` doesn't know and pinning errors to a key and
+/// location where possible.
+fn parse<T: DeserializeOwned + Serialize + Default>(
+    path: &Path,
+    text: &str,
+) -> Result<T, ConfigError> {
+    let invalid = |location, key: Option<&String>, message: String| ConfigError::Invalid {
+        path: path.to_path_buf(),
+        location,
+        key: key.cloned(),
+        message,
+    };
+
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        let suffix = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        let message = message
+            .strip_suffix(&suffix)
+            .unwrap_or(&message)
+            .to_string();
+        invalid(Some((e.line(), e.column())), None, message)
+    })?;
+    let Value::Object(map) = value else {
+        return Err(invalid(None, None, "expected a JSON object".to_string()));
+    };
+    let Ok(Value::Object(defaults)) = serde_json::to_value(T::default()) else {
+        unreachable!("configs serialize to a JSON object");
+    };
+
+    if let Some(key) = map.keys().find(|key| !defaults.contains_key(*key)) {
+        let known: Vec<&str> = defaults.keys().map(String::as_str).collect();
+        return Err(invalid(
+            locate_key(text, key),
+            Some(key),
+            format!("unknown key, expected one of: {}", known.join(", ")),
+        ));
+    }
+
+    serde_json::from_value(Value::Object(map.clone())).map_err(|e| {
+        // `from_value` errors carry no location, so find the first key that fails on its own.
+        let key = map.iter().map(|(key, _)| key).find(|&key| {
+            let mut probe = defaults.clone();
+            probe.insert(key.clone(), map[key].clone());
+            serde_json::from_value::<T>(Value::Object(probe)).is_err()
+        });
+        invalid(
+            key.and_then(|key| locate_key(text, key)),
+            key,
+            e.to_string(),
+        )
+    })
+}
+
+fn read_json<T: DeserializeOwned + Serialize + Default>(path: &Path) -> Result<T, ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(path, &text)
+}
+
+/// A project-local config, where every field is optional and overrides the home config.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PartialConfig {
+    pub version: Option<u32>,
+    pub c_compiler: Option<String>,
+    pub c_compiler_opts: Option<Vec<String>>,
+    pub source_code_filenames: Option<Vec<String>>,
+    pub test_folder_names: Option<Vec<String>>,
+    pub default_bin_output_name: Option<String>,
+    pub profiles: Option<HashMap<String, Profile>>,
+    pub pipes: Option<HashMap<String, Vec<String>>>,
+    pub regions: Option<HashMap<String, Vec<PathBuf>>>,
+    #[serde(default, deserialize_with = "positive_secs")]
+    pub time_limit_secs: Option<f64>,
+    #[serde(default, deserialize_with = "positive")]
+    pub cpu_time_limit_secs: Option<u64>,
+    #[serde(default, deserialize_with = "positive")]
+    pub memory_limit_mb: Option<u64>,
+    pub comparison: Option<Comparison>,
+    pub region_comparisons: Option<HashMap<String, Comparison>>,
+    pub checker: Option<String>,
+    pub hooks: Option<Hooks>,
+    pub region_hooks: Option<HashMap<String, Hooks>>,
+}
+
+impl PartialConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        read_json(path.as_ref())
+    }
+}
+
+/// Upgrades the config at `path` to [`CONFIG_VERSION`], keeping the original next to it as
+/// `.cvutie.v<N>.bak`. Returns the version it was migrated from, or `None` if it was up to date.
+/// Files that don't parse are left for [`Config::load`] to report.
+pub fn migrate(path: &Path) -> Result<Option<u32>, ConfigError> {
+    let io_error = |source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let text = std::fs::read_to_string(path).map_err(io_error)?;
+    let Ok(Value::Object(mut map)) = serde_json::from_str(&text) else {
+        return Ok(None);
+    };
+
+    let from = map.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if from >= CONFIG_VERSION {
+        return Ok(None);
+    }
+    for step in &MIGRATIONS[from as usize..] {
+        step(&mut map);
+    }
+    map.insert("version".to_string(), CONFIG_VERSION.into());
+
+    // Errors found here would point into the re-serialized text, not the user's file.
+    let migrated = serde_json::to_string_pretty(&map).map_err(ConfigError::Serialize)?;
+    let Ok(config) = parse::<Config>(path, &migrated) else {
+        return Ok(None);
+    };
+
+    let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(format!(".v{from}.bak"));
+    std::fs::copy(path, path.with_file_name(backup_name)).map_err(io_error)?;
+    config.save(path)?;
+    Ok(Some(from))
+}
+
+/// `~/.cvutie`, the config shared by every project.
+pub fn config_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(CONFIG_FILE))
+}
+
+/// Returns the `.cvutie` files in `start` and its ancestors, farthest first, skipping `exclude`.
+pub fn find_local_configs(start: &Path, exclude: Option<&Path>) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .filter(|path| path.is_file() && Some(path.as_path()) != exclude)
+        .collect();
+    found.reverse();
+    found
+}
+
 impl Config {
-    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
-        let file = std::fs::File::open(path)?;
-        let config = serde_json::from_reader(file)?;
-        Ok(config)
+    /// Overrides every field set in `local`. Pipes and regions are merged by name.
+    pub fn merge(&mut self, local: PartialConfig) {
+        fn merge_maps<V>(base: &mut Option<HashMap<String, V>>, local: Option<HashMap<String, V>>) {
+            if let Some(local) = local {
+                base.get_or_insert_with(HashMap::new).extend(local);
+            }
+        }
+
+        if let Some(c_compiler) = local.c_compiler {
+            self.c_compiler = c_compiler;
+        }
+        if let Some(c_compiler_opts) = local.c_compiler_opts {
+            self.c_compiler_opts = c_compiler_opts;
+        }
+        if let Some(source_code_filenames) = local.source_code_filenames {
+            self.source_code_filenames = source_code_filenames;
+        }
+        if let Some(test_folder_names) = local.test_folder_names {
+            self.test_folder_names = test_folder_names;
+        }
+        if let Some(default_bin_output_name) = local.default_bin_output_name {
+            self.default_bin_output_name = default_bin_output_name;
+        }
+        if let Some(profiles) = local.profiles {
+            self.profiles.extend(profiles);
+        }
+        merge_maps(&mut self.pipes, local.pipes);
+        merge_maps(&mut self.regions, local.regions);
+        self.time_limit_secs = local.time_limit_secs.or(self.time_limit_secs);
+        self.cpu_time_limit_secs = local.cpu_time_limit_secs.or(self.cpu_time_limit_secs);
+        self.memory_limit_mb = local.memory_limit_mb.or(self.memory_limit_mb);
+        if let Some(comparison) = local.comparison {
+            self.comparison = comparison;
+        }
+        merge_maps(&mut self.region_comparisons, local.region_comparisons);
+        if let Some(checker) = local.checker {
+            self.checker = Some(checker);
+        }
+        if let Some(hooks) = local.hooks {
+            let base = &mut self.hooks;
+            base.pre_compile = hooks.pre_compile.or(base.pre_compile.take());
+            base.post_compile = hooks.post_compile.or(base.post_compile.take());
+            base.pre_test = hooks.pre_test.or(base.pre_test.take());
+            base.post_test = hooks.post_test.or(base.post_test.take());
+        }
+        merge_maps(&mut self.region_hooks, local.region_hooks);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        read_json(path.as_ref())
+    }
+
+    /// Writes the config to a temporary file next to `path` and renames it into place, so an
+    /// interrupted save never leaves a truncated config behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let json = serde_json::to_vec_pretty(self).map_err(ConfigError::Serialize)?;
+
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp = path.with_file_name(tmp_name);
+
+        let io_error = |source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut file = std::fs::File::create(&tmp).map_err(io_error)?;
+        file.write_all(&json)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|source| {
+                let _ = std::fs::remove_file(&tmp);
+                io_error(source)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_leaves_invalid_files_for_load() {
+        let dir = crate::temp::dir("migrate").unwrap();
+        let path = dir.join(CONFIG_FILE);
+        std::fs::write(&path, r#"{"c_compiler": 5}"#).unwrap();
+
+        assert!(matches!(migrate(&path), Ok(None)));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{"c_compiler": 5}"#
+        );
+        let error = Config::load(&path).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.to_string().contains(":1:"), "{error}");
+    }
+
+    #[test]
+    fn every_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len(), CONFIG_VERSION as usize);
+    }
+
+    #[test]
+    fn migration_to_v2_keeps_a_chosen_compiler() {
+        let migrate_v1 = |compiler: &str| {
+            let mut map = Map::new();
+            map.insert("c_compiler".to_string(), compiler.into());
+            map.insert("c_compiler_opts".to_string(), C_COMPILER_OPTS_V1.into());
+            MIGRATIONS[1](&mut map);
+            assert_eq!(map["c_compiler_opts"], Value::from(C_COMPILER_OPTS_DEFAULT));
+            map["c_compiler"].clone()
+        };
+        assert_eq!(migrate_v1("g++"), C_COMPILER_DEFAULT);
+        assert_eq!(migrate_v1("clang"), "clang");
+    }
+
+    #[test]
+    fn rejects_unknown_profile_key() {
+        let text = r#"{"profiles": {"old": {"compiler": "gcc", "sdt": "c99", "flags": []}}}"#;
+        let error = parse::<Config>(Path::new(".cvutie"), text).unwrap_err();
+        assert!(error.to_string().contains("sdt"), "{error}");
+    }
+
+    #[test]
+    fn rejects_unknown_hook() {
+        let error = parse::<Config>(
+            Path::new(".cvutie"),
+            r#"{"hooks": {"pre_compil": "exit 1"}}"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("pre_compil"), "{error}");
+    }
+
+    #[test]
+    fn rejects_limits_that_are_not_positive() {
+        let path = Path::new(".cvutie");
+        for text in [
+            r#"{"time_limit_secs": -2}"#,
+            r#"{"time_limit_secs": 0}"#,
+            r#"{"cpu_time_limit_secs": 0}"#,
+        ] {
+            assert!(parse::<Config>(path, text).is_err(), "{text}");
+            assert!(parse::<PartialConfig>(path, text).is_err(), "{text}");
+        }
+        let local = parse::<PartialConfig>(path, r#"{"time_limit_secs": 0.5}"#).unwrap();
+        assert_eq!(local.time_limit_secs, Some(0.5));
+        assert_eq!(local.memory_limit_mb, None);
     }
 
-    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
-        let file = std::fs::File::create(path)?;
-        serde_json::to_writer_pretty(file, self)?;
-        Ok(())
+    #[test]
+    fn local_hooks_override_home_hooks_field_by_field() {
+        let mut config: Config =
+            serde_json::from_str(r#"{"hooks": {"pre_compile": "make header"}}"#).unwrap();
+        let local: PartialConfig =
+            serde_json::from_str(r#"{"hooks": {"post_test": "archive"}}"#).unwrap();
+        config.merge(local);
+        assert_eq!(config.hooks.pre_compile.as_deref(), Some("make header"));
+        assert_eq!(config.hooks.post_test.as_deref(), Some("archive"));
     }
 }
@@ -0,0 +1,153 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+    process::Command,
+};
+
+use crate::config::Config;
+
+fn to_object(config: &Config) -> Result<serde_json::Map<String, Value>> {
+    match serde_json::to_value(config)? {
+        Value::Object(map) => Ok(map),
+        _ => unreachable!("Config serializes to a JSON object"),
+    }
+}
+
+fn unknown_key(key: &str, map: &serde_json::Map<String, Value>) -> anyhow::Error {
+    let keys: Vec<&str> = map.keys().map(String::as_str).collect();
+    anyhow::anyhow!("Unknown key `{key}`. Known keys: {}", keys.join(", "))
+}
+
+/// Prints the whole config as JSON.
+pub fn show(config: &Config) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(config)?);
+    Ok(())
+}
+
+/// Prints the value of `key`; strings are printed without quotes.
+pub fn get(config: &Config, key: &str) -> Result<()> {
+    let map = to_object(config)?;
+    match map.get(key) {
+        Some(Value::String(s)) => println!("{s}"),
+        Some(value) => println!("{}", serde_json::to_string_pretty(value)?),
+        None => return Err(unknown_key(key, &map)),
+    }
+    Ok(())
+}
+
+/// Sets `key` to `value`, or appends `value` to it when `append` is set. `value` is parsed as JSON
+/// and taken as a plain string otherwise, or when `key` holds strings and `value` is a number, a
+/// boolean or `null`. The result must still deserialize into a [`Config`].
+pub fn set(config: &Config, key: &str, value: &str, append: bool) -> Result<Config> {
+    let mut map = to_object(config)?;
+    let raw = value;
+    let value = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+
+    let Some(slot) = map.get_mut(key) else {
+        return Err(unknown_key(key, &map));
+    };
+    let holds_strings = match (append, &*slot) {
+        (false, Value::String(_)) => true,
+        (true, Value::Array(items)) => !items.is_empty() && items.iter().all(Value::is_string),
+        _ => false,
+    };
+    let value = match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) if holds_strings => {
+            Value::String(raw.to_string())
+        }
+        value => value,
+    };
+    match (append, slot) {
+        (false, slot) => *slot = value,
+        (true, Value::Array(items)) => match value {
+            Value::Array(values) => items.extend(values),
+            value => items.push(value),
+        },
+        (true, slot @ Value::Null) => *slot = Value::Array(vec![value]),
+        (true, _) => bail!("`{key}` is not a list, `--append` can't be used"),
+    }
+
+    serde_json::from_value(Value::Object(map)).with_context(|| format!("Invalid value for `{key}`"))
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [Y/n] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(!answer.trim().eq_ignore_ascii_case("n"))
+}
+
+/// Opens `$EDITOR` on a copy of the config at `path` and moves it back once it validates.
+pub fn edit(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let mut draft_name = path.file_name().unwrap_or_default().to_os_string();
+    draft_name.push(".edit");
+    let draft = path.with_file_name(draft_name);
+    if path.exists() {
+        std::fs::copy(path, &draft)?;
+    } else {
+        Config::default().save(&draft)?;
+    }
+
+    let result = (|| loop {
+        // `$EDITOR` may carry arguments, e.g. `code --wait`.
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$0\""))
+            .arg(&draft)
+            .status()
+            .with_context(|| format!("Failed to run editor `{editor}`"))?;
+        if !status.success() {
+            bail!("Editor `{editor}` exited with {status}");
+        }
+
+        match Config::load(&draft) {
+            // The draft sits next to `path`, so renaming it is atomic and keeps the formatting.
+            Ok(_) => break Ok(std::fs::rename(&draft, path)?),
+            Err(e) => {
+                eprintln!("{e}");
+                if !confirm("Re-open the editor?")? {
+                    bail!("Discarded changes to `{}`", path.display());
+                }
+            }
+        }
+    })();
+
+    let _ = std::fs::remove_file(&draft);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_strings_for_string_keys() {
+        let config = set(&Config::default(), "default_bin_output_name", "123", false).unwrap();
+        assert_eq!(config.default_bin_output_name, "123");
+        let config = set(&Config::default(), "c_compiler", "5", false).unwrap();
+        assert_eq!(config.c_compiler, "5");
+    }
+
+    #[test]
+    fn appended_numbers_are_strings_for_string_lists() {
+        let config = set(&Config::default(), "test_folder_names", "2024", true).unwrap();
+        assert_eq!(
+            config.test_folder_names.last().map(String::as_str),
+            Some("2024")
+        );
+    }
+
+    #[test]
+    fn numbers_stay_numbers_for_number_keys() {
+        let config = set(&Config::default(), "memory_limit_mb", "256", false).unwrap();
+        assert_eq!(config.memory_limit_mb, Some(256));
+        assert!(set(&Config::default(), "memory_limit_mb", "lots", false).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::style;
+
+const CONTEXT: usize = 3;
+/// Upper bound on the LCS table size; larger inputs fall back to a plain delete/insert diff.
+const MAX_TABLE_CELLS: usize = 4_000_000;
+/// Rendered diffs are cut off after this many lines.
+const MAX_RENDERED_LINES: usize = 80;
+
+#[derive(PartialEq)]
+struct Line<'a> {
+    text: &'a str,
+    newline: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One diff operation along with the expected (`a`) and actual (`b`) line index it sits at.
+#[derive(Clone, Copy)]
+struct Op {
+    kind: Kind,
+    a: usize,
+    b: usize,
+}
+
+fn split_lines(text: &str) -> Vec<Line<'_>> {
+    text.split_inclusive('\n')
+        .map(|line| match line.strip_suffix('\n') {
+            Some(text) => Line {
+                text,
+                newline: true,
+            },
+            None => Line {
+                text: line,
+                newline: false,
+            },
+        })
+        .collect()
+}
+
+/// Returns the 1-based line and column of the first difference between `expected` and `actual`.
+pub fn first_difference(expected: &str, actual: &str) -> Option<(usize, usize)> {
+    let (a, b) = (split_lines(expected), split_lines(actual));
+    let line = (0..a.len().max(b.len())).find(|&i| a.get(i) != b.get(i))?;
+
+    let column = match (a.get(line), b.get(line)) {
+        (Some(x), Some(y)) => {
+            let common = x
+                .text
+                .chars()
+                .zip(y.text.chars())
+                .take_while(|(c, d)| c == d)
+                .count();
+            common + 1
+        }
+        _ => 1,
+    };
+    Some((line + 1, column))
+}
+
+fn diff_ops(a: &[Line], b: &[Line]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (n, m) = (a.len() - prefix - suffix, b.len() - prefix - suffix);
+
+    let mut ops: Vec<Op> = (0..prefix)
+        .map(|i| Op {
+            kind: Kind::Equal,
+            a: i,
+            b: i,
+        })
+        .collect();
+
+    let (mid_a, mid_b) = (&a[prefix..prefix + n], &b[prefix..prefix + m]);
+    if (n + 1).saturating_mul(m + 1) <= MAX_TABLE_CELLS {
+        // lcs[i][j] is the LCS length of mid_a[i..] and mid_b[j..].
+        let width = m + 1;
+        let mut lcs = vec![0u32; (n + 1) * width];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * width + j] = if mid_a[i] == mid_b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            let kind = if i < n && j < m && mid_a[i] == mid_b[j] {
+                Kind::Equal
+            } else if i < n && (j == m || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+                Kind::Delete
+            } else {
+                Kind::Insert
+            };
+            ops.push(Op {
+                kind,
+                a: prefix + i,
+                b: prefix + j,
+            });
+            if kind != Kind::Insert {
+                i += 1;
+            }
+            if kind != Kind::Delete {
+                j += 1;
+            }
+        }
+    } else {
+        ops.extend((0..n).map(|i| Op {
+            kind: Kind::Delete,
+            a: prefix + i,
+            b: prefix,
+        }));
+        ops.extend((0..m).map(|j| Op {
+            kind: Kind::Insert,
+            a: prefix + n,
+            b: prefix + j,
+        }));
+    }
+
+    ops.extend((0..suffix).map(|k| Op {
+        kind: Kind::Equal,
+        a: prefix + n + k,
+        b: prefix + m + k,
+    }));
+    ops
+}
+
+/// Groups changed operations into hunks, returned as index ranges into `ops`.
+fn hunks(ops: &[Op]) -> Vec<std::ops::Range<usize>> {
+    let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+    for (idx, _) in ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.kind != Kind::Equal)
+    {
+        let start = idx.saturating_sub(CONTEXT);
+        let end = (idx + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+    hunks
+}
+
+/// Makes trailing whitespace and carriage returns visible.
+fn visible(text: &str) -> String {
+    let trailing = text.trim_end_matches([' ', '\t', '\r']).len();
+    text.char_indices()
+        .map(|(i, c)| match c {
+            '\r' => '␍',
+            ' ' if i >= trailing => '·',
+            '\t' if i >= trailing => '→',
+            c => c,
+        })
+        .collect()
+}
+
+/// Renders a colored unified diff of `expected` against `actual`, marking the first difference.
+pub fn unified(expected: &str, actual: &str) -> String {
+    let (a, b) = (split_lines(expected), split_lines(actual));
+    let ops = diff_ops(&a, &b);
+    let first = first_difference(expected, actual);
+
+    let mut out = vec![style::red("--- expected"), style::green("+++ actual")];
+    let mut marked = false;
+    for hunk in hunks(&ops) {
+        let ops = &ops[hunk];
+        let a_len = ops.iter().filter(|op| op.kind != Kind::Insert).count();
+        let b_len = ops.iter().filter(|op| op.kind != Kind::Delete).count();
+        let a_start = ops[0].a + usize::from(a_len > 0);
+        let b_start = ops[0].b + usize::from(b_len > 0);
+        out.push(style::cyan(&format!(
+            "@@ -{a_start},{a_len} +{b_start},{b_len} @@"
+        )));
+
+        for op in ops {
+            let (sign, line) = match op.kind {
+                Kind::Equal => (' ', &a[op.a]),
+                Kind::Delete => ('-', &a[op.a]),
+                Kind::Insert => ('+', &b[op.b]),
+            };
+            let rendered = format!("{sign}{}", visible(line.text));
+            out.push(match op.kind {
+                Kind::Equal => rendered,
+                Kind::Delete => style::red(&rendered),
+                Kind::Insert => style::green(&rendered),
+            });
+            if !line.newline {
+                out.push(style::yellow("\\ No newline at end of file"));
+            }
+
+            let index = if op.kind == Kind::Insert { op.b } else { op.a };
+            if let Some((row, column)) = first.filter(|_| op.kind != Kind::Equal && !marked) {
+                if index + 1 == row {
+                    marked = true;
+                    out.push(style::yellow(&format!(
+                        "{}^ first difference at line {row}, column {column}",
+                        " ".repeat(column)
+                    )));
+                }
+            }
+        }
+    }
+
+    if out.len() > MAX_RENDERED_LINES {
+        let hidden = out.len() - MAX_RENDERED_LINES;
+        out.truncate(MAX_RENDERED_LINES);
+        out.push(format!("... {hidden} more lines"));
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The operations for `expected` against `actual`, as `=`, `-` and `+`.
+    fn ops(expected: &str, actual: &str) -> String {
+        diff_ops(&split_lines(expected), &split_lines(actual))
+            .iter()
+            .map(|op| match op.kind {
+                Kind::Equal => '=',
+                Kind::Delete => '-',
+                Kind::Insert => '+',
+            })
+            .collect()
+    }
+
+    fn plain(expected: &str, actual: &str) -> String {
+        style::strip(&unified(expected, actual))
+    }
+
+    #[test]
+    fn walks_the_longest_common_subsequence() {
+        assert_eq!(ops("a\nb\nc\n", "a\nb\nc\n"), "===");
+        assert_eq!(ops("a\nb\nc\n", "a\nx\nc\n"), "=-+=");
+        assert_eq!(ops("a\nb\nc\nd\n", "b\nd\ne\n"), "-=-=+");
+        assert_eq!(ops("a\nb\n", "a\nx\nb\n"), "=+=");
+        assert_eq!(ops("a\nb\n", "b\n"), "-=");
+    }
+
+    #[test]
+    fn keeps_distant_changes_in_separate_hunks() {
+        let expected: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let actual: String = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                18 => "eighteen\n".to_string(),
+                i => format!("{i}\n"),
+            })
+            .collect();
+        let ops = diff_ops(&split_lines(&expected), &split_lines(&actual));
+        assert_eq!(hunks(&ops), [0..6, 15..22]);
+    }
+
+    #[test]
+    fn insert_only() {
+        assert_eq!(
+            plain("a\nb\n", "a\nx\nb\n"),
+            "--- expected\n+++ actual\n@@ -1,2 +1,3 @@\n a\n+x\n ^ first difference at line 2, column 1\n b"
+        );
+        assert_eq!(
+            plain("", "x\n"),
+            "--- expected\n+++ actual\n@@ -0,0 +1,1 @@\n+x\n ^ first difference at line 1, column 1"
+        );
+    }
+
+    #[test]
+    fn delete_only() {
+        assert_eq!(
+            plain("a\n", ""),
+            "--- expected\n+++ actual\n@@ -1,1 +0,0 @@\n-a\n ^ first difference at line 1, column 1"
+        );
+    }
+
+    #[test]
+    fn marks_carriage_returns_and_trailing_whitespace() {
+        assert_eq!(
+            plain("1\n2\n", "1\r\n2\r\n"),
+            "--- expected\n+++ actual\n@@ -1,2 +1,2 @@\n-1\n  ^ first difference at line 1, column 2\n-2\n+1␍\n+2␍"
+        );
+        assert_eq!(
+            plain("a b\n", "a b \t\n"),
+            "--- expected\n+++ actual\n@@ -1,1 +1,1 @@\n-a b\n    ^ first difference at line 1, column 4\n+a b·→"
+        );
+    }
+
+    #[test]
+    fn marks_a_missing_final_newline() {
+        assert_eq!(
+            plain("1\n2\n", "1\n2"),
+            "--- expected\n+++ actual\n@@ -1,2 +1,2 @@\n 1\n-2\n  ^ first difference at line 2, column 2\n+2\n\\ No newline at end of file"
+        );
+    }
+
+    #[test]
+    fn finds_the_first_difference() {
+        assert_eq!(first_difference("a\nb\n", "a\nb\n"), None);
+        assert_eq!(first_difference("abc\n", "abd\n"), Some((1, 3)));
+        assert_eq!(first_difference("a\n", "a\nb\n"), Some((2, 1)));
+        assert_eq!(first_difference("a\n", "a"), Some((1, 2)));
+        assert_eq!(first_difference("žluť\n", "žlut\n"), Some((1, 4)));
+    }
+}
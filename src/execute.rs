@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use std::{
+    ffi::OsString,
+    fmt,
+    io::Read,
+    os::unix::process::ExitStatusExt,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::config::Config;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+const SIGXCPU: i32 = 24;
+
+/// A program to run, along with its arguments and extra environment variables.
+pub struct Invocation {
+    pub program: PathBuf,
+    pub args: Vec<OsString>,
+    pub env: Vec<(String, String)>,
+}
+
+impl Invocation {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+        }
+    }
+}
+
+/// `secs` as a wall-clock limit, failing rather than panicking on values a `Duration` can't hold.
+pub fn wall_time(secs: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(secs)
+        .with_context(|| format!("Invalid time limit of {secs} seconds"))
+}
+
+/// Resource limits applied to a single run.
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    pub wall_time: Option<Duration>,
+    pub cpu_time_secs: Option<u64>,
+    pub memory_mb: Option<u64>,
+}
+
+impl Limits {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            wall_time: config.time_limit_secs.map(wall_time).transpose()?,
+            cpu_time_secs: config.cpu_time_limit_secs,
+            memory_mb: config.memory_limit_mb,
+        })
+    }
+
+    /// Wraps `invocation` in a shell that sets the rlimits before `exec`ing it.
+    fn command(&self, invocation: &Invocation) -> Command {
+        let mut ulimits = String::new();
+        if let Some(secs) = self.cpu_time_secs {
+            // A soft limit below the hard one makes the kernel send SIGXCPU instead of SIGKILL.
+            ulimits.push_str(&format!(
+                "ulimit -S -t {secs} && ulimit -H -t {} && ",
+                secs + 1
+            ));
+        }
+        if let Some(mb) = self.memory_mb {
+            ulimits.push_str(&format!("ulimit -v {} && ", mb * 1024));
+        }
+
+        let mut command = if ulimits.is_empty() {
+            Command::new(&invocation.program)
+        } else {
+            let mut command = Command::new("sh");
+            command
+                .arg("-c")
+                .arg(format!("{ulimits}exec \"$0\" \"$@\""))
+                .arg(&invocation.program);
+            command
+        };
+        command
+            .args(&invocation.args)
+            .envs(invocation.env.iter().map(|(k, v)| (k, v)));
+        command
+    }
+}
+
+/// How a run ended.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Exited(i32),
+    Signaled(i32),
+    WallTimeout(Duration),
+    CpuTimeout(u64),
+}
+
+impl Verdict {
+    fn from_status(status: ExitStatus, limits: &Limits) -> Self {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => Self::Exited(code),
+            (None, Some(SIGXCPU)) => Self::CpuTimeout(limits.cpu_time_secs.unwrap_or_default()),
+            (None, Some(signal)) => Self::Signaled(signal),
+            (None, None) => Self::Exited(-1),
+        }
+    }
+
+    /// True when the program terminated on its own, regardless of its exit code.
+    pub fn exited(&self) -> bool {
+        matches!(self, Self::Exited(_))
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exited with code {code}"),
+            Self::Signaled(signal) => match signal_name(*signal) {
+                Some((name, meaning)) => write!(f, "killed by {name} ({meaning})"),
+                None => write!(f, "killed by signal {signal}"),
+            },
+            Self::WallTimeout(limit) => {
+                write!(
+                    f,
+                    "timed out after {:.2}s wall-clock time",
+                    limit.as_secs_f64()
+                )
+            }
+            Self::CpuTimeout(secs) => write!(f, "exceeded the CPU time limit of {secs}s"),
+        }
+    }
+}
+
+fn signal_name(signal: i32) -> Option<(&'static str, &'static str)> {
+    Some(match signal {
+        4 => ("SIGILL", "illegal instruction"),
+        6 => ("SIGABRT", "aborted"),
+        7 => ("SIGBUS", "bus error"),
+        8 => ("SIGFPE", "arithmetic exception"),
+        9 => ("SIGKILL", "killed"),
+        11 => ("SIGSEGV", "segmentation fault"),
+        13 => ("SIGPIPE", "broken pipe"),
+        15 => ("SIGTERM", "terminated"),
+        _ => return None,
+    })
+}
+
+pub struct Run {
+    pub verdict: Verdict,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub duration: Duration,
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+fn wait(child: &mut Child, limits: &Limits, start: Instant) -> Result<Verdict> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Verdict::from_status(status, limits));
+        }
+        if let Some(limit) = limits.wall_time.filter(|&limit| start.elapsed() >= limit) {
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(Verdict::WallTimeout(limit));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Runs `invocation` under `limits` with `stdin`. With `capture` set, stdout and stderr are
+/// collected into the returned [`Run`], otherwise they are inherited.
+pub fn run(invocation: &Invocation, stdin: Stdio, capture: bool, limits: &Limits) -> Result<Run> {
+    let output = || {
+        if capture {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        }
+    };
+
+    let start = Instant::now();
+    let mut child = limits
+        .command(invocation)
+        .stdin(stdin)
+        .stdout(output())
+        .stderr(output())
+        .spawn()
+        .with_context(|| format!("Failed to execute `{}`", invocation.program.display()))?;
+
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let verdict = wait(&mut child, limits, start)?;
+    let duration = start.elapsed();
+
+    Ok(Run {
+        verdict,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+        duration,
+    })
+}
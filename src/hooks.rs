@@ -0,0 +1,84 @@
+use anyhow::{bail, Context, Result};
+use std::{path::Path, process::Command};
+
+use crate::{
+    config::{Config, Hooks},
+    region, style,
+};
+
+/// A point in `compile` and `test-all` where hooks run.
+#[derive(Clone, Copy)]
+pub enum Hook {
+    PreCompile,
+    PostCompile,
+    PreTest,
+    PostTest,
+}
+
+impl Hook {
+    /// The name of the hook in [`Hooks`].
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PreCompile => "pre_compile",
+            Hook::PostCompile => "post_compile",
+            Hook::PreTest => "pre_test",
+            Hook::PostTest => "post_test",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Hook::PreCompile => hooks.pre_compile.as_deref(),
+            Hook::PostCompile => hooks.post_compile.as_deref(),
+            Hook::PreTest => hooks.pre_test.as_deref(),
+            Hook::PostTest => hooks.post_test.as_deref(),
+        }
+    }
+}
+
+/// Runs the global `hook` and then the one of the region `dir` belongs to, with `dir` as the
+/// working directory. Besides `env`, hooks get `CVUTIE_HOOK` and `CVUTIE_PROJECT`.
+pub fn run(config: &Config, hook: Hook, dir: &Path, env: &[(&str, String)]) -> Result<()> {
+    let region =
+        region::containing(config, dir).and_then(|name| config.region_hooks.as_ref()?.get(name));
+    let commands = [Some(&config.hooks), region]
+        .into_iter()
+        .flatten()
+        .filter_map(|hooks| hook.command(hooks));
+
+    let project = std::path::absolute(dir)?;
+    for command in commands {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir)
+            .env("CVUTIE_HOOK", hook.name())
+            .env("CVUTIE_PROJECT", &project)
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .status()
+            .with_context(|| format!("Failed to run `{}` hook `{command}`", hook.name()))?;
+        if !status.success() {
+            bail!(
+                "`{}` hook `{command}` failed in `{}` ({status})",
+                hook.name(),
+                dir.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Like [`run`], but only warns on failure since there is nothing left to abort.
+pub fn run_post(config: &Config, hook: Hook, dir: &Path, env: &[(&str, String)]) {
+    if let Err(e) = run(config, hook, dir, env) {
+        eprintln!("{} {e:#}", style::yellow("warning:"));
+    }
+}
+
+/// Runs the `post_compile` hooks of the project in `dir` with `binary` in `CVUTIE_BINARY`.
+pub fn run_post_compile(config: &Config, dir: &Path, binary: &Path) {
+    // Hooks run in `dir`, where a path relative to the current directory would point elsewhere.
+    let binary = std::path::absolute(binary).unwrap_or_else(|_| binary.to_path_buf());
+    let env = [("CVUTIE_BINARY", binary.display().to_string())];
+    run_post(config, Hook::PostCompile, dir, &env);
+}
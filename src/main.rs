@@ -1,18 +1,43 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+mod checker;
+mod compare;
+mod compile;
 mod config;
+mod config_cmd;
+mod diff;
+mod execute;
+mod hooks;
+mod memcheck;
+mod pipe;
+mod pool;
+mod region;
+mod report;
+mod samples;
+mod sanitizer;
+mod scaffold;
+mod style;
+mod temp;
+mod testing;
 
-use clap::{Parser, Subcommand};
-use config::Config;
-
-const CONFIG: &str = ".cvutie";
+use clap::{Args, Parser, Subcommand};
+use config::{config_path, Config, PartialConfig};
+use execute::{Invocation, Limits, Verdict};
+use hooks::Hook;
+use pipe::PipeOptions;
+use std::{
+    path::{Path, PathBuf},
+    process::{ExitCode, Stdio},
+};
+use testing::TestOptions;
 
 #[derive(Parser)]
 #[command(name = "cvutie")]
 #[command(about = "CLI for (initially) compiling and testing BIK-PA1 projects.", long_about = None)]
 #[command(version = "0.1")]
 struct Cli {
+    /// Region to operate on when no target is given
     #[arg(short, long, global = true)]
-    region: String,
+    region: Option<String>,
 
     #[command(subcommand)]
     command: Commands,
@@ -22,28 +47,89 @@ struct Cli {
 enum Commands {
     /// Compile a target C file
     Compile {
-        // Try read region if not a path, else target directory path
-        target: String,
+        /// Project directory or region name (defaults to `--region`, then the current directory)
+        target: Option<String>,
+        /// Binary name, relative to the project directory
         #[arg(long, short)]
         output: Option<String>,
+
+        /// Build profile from `profiles` to use instead of `c_compiler`/`c_compiler_opts`
+        #[arg(long, short)]
+        profile: Option<String>,
     },
 
     /// Execute a target binary.
-    Execute { target: String },
+    Execute {
+        /// Binary, project directory or region name (defaults to `--region`, then the current
+        /// directory)
+        target: Option<String>,
+
+        /// Wall-clock limit in seconds (overrides `time_limit_secs`)
+        #[arg(long, value_parser = parse_secs)]
+        time_limit: Option<f64>,
+
+        /// CPU time limit in seconds (overrides `cpu_time_limit_secs`)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        cpu_time_limit: Option<u64>,
+
+        /// Address space limit in megabytes (overrides `memory_limit_mb`)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        memory_limit: Option<u64>,
+
+        /// Run under valgrind's memcheck and report leaks and invalid accesses
+        #[arg(long)]
+        memcheck: bool,
+    },
 
-    /// Allows to pipe together CVUTie commands and python/bash scripts to create ad-hoc commands.
+    /// Run a pipe from `pipes`, feeding the output of each CVUTie command or python/shell script to
+    /// the next, or manage saved pipes
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Pipe {
-        #[arg(long, short)]
-        output: Option<String>,
+        #[command(subcommand)]
+        action: Option<PipeAction>,
 
-        commands: Vec<String>,
+        /// Name of the pipe to run
+        #[arg(required = true)]
+        name: Option<String>,
+
+        #[command(flatten)]
+        run: PipeRun,
     },
 
     /// Run tests for compilation and execution across the entire sub-directory
-    TestAll { target: String },
+    TestAll {
+        /// Directory or region name to search for tests (defaults to `--region`, then the current
+        /// directory)
+        target: Option<String>,
+
+        /// Build with AddressSanitizer and UBSan and fail cases that trigger a report
+        #[arg(long)]
+        sanitize: bool,
+
+        /// Run every case under valgrind's memcheck and fail cases that leak or access invalid
+        /// memory
+        #[arg(long, conflicts_with = "sanitize")]
+        memcheck: bool,
+
+        /// Number of test cases to run in parallel (defaults to the number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Also write the results of every case to `--report-file` in this format
+        #[arg(long, value_enum, requires = "report_file")]
+        report: Option<report::Format>,
 
-    /// Add directory
+        /// Where to write the `--report`
+        #[arg(long, requires = "report")]
+        report_file: Option<PathBuf>,
+    },
+
+    /// Define a region from directories, or manage existing regions
+    #[command(args_conflicts_with_subcommands = true)]
     Region {
+        #[command(subcommand)]
+        action: Option<RegionAction>,
+
         /// Directories to add to a Region
         folders: Vec<String>,
 
@@ -55,125 +141,461 @@ enum Commands {
         #[arg(long = "force")]
         force: bool,
     },
+
+    /// Create a project with a source skeleton and empty test folders, adding it to `--region`
+    New {
+        /// Directory of the new project
+        name: PathBuf,
+
+        /// Also create an empty project-local `.cvutie`
+        #[arg(long)]
+        local_config: bool,
+    },
+
+    /// Manage Progtest sample archives
+    Samples {
+        #[command(subcommand)]
+        action: SamplesAction,
+    },
+
+    /// Manage the `~/.cvutie` config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
 }
 
-fn get_configuration() -> Config {
-    let home = std::env::var_os("HOME");
-    if home.is_none() {
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Write a config file with defaults
+    Init {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print the effective config, including project-local overrides
+    Show,
+
+    /// Print the effective value of a key
+    Get { key: String },
+
+    /// Set a key in `~/.cvutie`. The value is parsed as JSON, or taken as a string otherwise
+    Set {
+        key: String,
+
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+
+        /// Append to a list instead of replacing it
+        #[arg(short, long)]
+        append: bool,
+    },
+
+    /// Open `~/.cvutie` in `$EDITOR`, validating it before it is saved
+    Edit,
+}
+
+#[derive(Subcommand)]
+enum PipeAction {
+    /// Save the steps after `--` as a pipe. Prefix a step with `@each` or `@parallel` to run it
+    /// once for every project, one at a time or all at once
+    Save {
+        name: String,
+
+        #[arg(last = true, required = true)]
+        steps: Vec<String>,
+
+        /// Required for overwriting an existing pipe
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List all pipes
+    List,
+
+    /// Show the steps of a pipe
+    Show { name: String },
+
+    /// Delete a pipe
+    Delete { name: String },
+
+    /// Run a pipe
+    Run {
+        name: String,
+
+        #[command(flatten)]
+        run: PipeRun,
+    },
+}
+
+#[derive(Args)]
+struct PipeRun {
+    /// Project directory or region name to run the pipe for, once per project (defaults to
+    /// `--region`, then the current directory)
+    target: Option<String>,
+
+    /// Write the output of the last step to this file instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Test case for `{test_in}` and `{test_out}`, e.g. `0003` or `ENG/0003`
+    #[arg(long)]
+    case: Option<String>,
+
+    /// Print the commands the steps would run instead of running them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Subcommand)]
+enum SamplesAction {
+    /// Unpack a `.tgz` or `.zip` sample archive into the project's test folders
+    Import {
+        archive: PathBuf,
+
+        /// Project directory (defaults to the current directory)
+        dir: Option<PathBuf>,
+
+        /// Overwrite test files that already exist with different contents
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegionAction {
+    /// List all regions
+    List,
+
+    /// Show the folders of a region
+    Show { name: String },
+
+    /// Remove folders from a region, or the whole region if no folders are given
+    Remove { name: String, folders: Vec<String> },
+
+    /// Rename a region
+    Rename { old: String, new: String },
+}
+
+fn parse_secs(value: &str) -> Result<f64, String> {
+    let secs = value.parse().map_err(|e| format!("{e}"))?;
+    config::check_secs(secs)
+}
+
+/// Loads `~/.cvutie`, writing one with defaults if it doesn't exist and `create_missing` is set.
+fn get_configuration(create_missing: bool) -> Config {
+    let Some(path) = config_path() else {
         eprintln!("Could not find or read home directory. Please ensure $HOME environment variable is set");
         return Config::default();
     };
-    let home = home.unwrap();
-    let home = home.to_string_lossy();
 
-    let config_path = format!("{}/{}", home, CONFIG);
+    match config::migrate(&path) {
+        Ok(Some(from)) => println!(
+            "Migrated `{}` from version {from} to {}, the original was backed up next to it",
+            path.display(),
+            config::CONFIG_VERSION
+        ),
+        Err(e) if !e.is_not_found() => eprintln!("Failed to migrate config: {e}"),
+        _ => {}
+    }
 
-    match Config::load(config_path) {
+    match Config::load(&path) {
         Ok(config) => config,
-        Err(_) => {
+        Err(e) if e.is_not_found() && !create_missing => Config::default(),
+        Err(e) if e.is_not_found() => {
             println!(
-                "Couldn't detect a `.cvutie` file in `{home}`. Creating a config file with defaults.."
+                "Couldn't detect `{}`. Creating a config file with defaults..",
+                path.display()
             );
             let config = Config::default();
-            if let Err(e) = config.save(home.to_string()) {
-                if e.downcast_ref::<std::io::Error>().is_some() {
-                    println!("Failed to create config file: No permission to write in home directory. Changing config won't be possible.");
-                } else if e.downcast_ref::<serde_json::Error>().is_some() {
-                    eprintln!(
-                        "Failed to serialize config: Internal error occurred while writing config."
-                    );
-                    std::process::exit(1);
-                } else {
-                    eprintln!("An unexpected error occurred");
-                    std::process::exit(1);
-                };
+            if let Err(e) = config.save(&path) {
+                eprintln!("Failed to create config file: {e}. Changing config won't be possible.");
             }
             config
         }
+        Err(e) => {
+            eprintln!("{e}. Using defaults, the file is left untouched.");
+            Config::default()
+        }
     }
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// Layers every project-local `.cvutie` from the current directory upwards over `home`.
+fn local_configuration(mut config: Config) -> Result<Config> {
+    let cwd = std::env::current_dir()?;
+    let home = config_path();
+    for path in config::find_local_configs(&cwd, home.as_deref()) {
+        config.merge(PartialConfig::load(&path)?);
+    }
+    Ok(config)
+}
 
-    if cli.verbose {
-        println!("Verbose mode enabled");
-        println!("Using repository at: {}", cli.git_dir);
+/// Applies `change` to `~/.cvutie` and saves it. Unlike [`get_configuration`], this refuses to
+/// touch an existing file that doesn't validate.
+fn update_config(change: impl FnOnce(&mut Config) -> Result<()>) -> Result<()> {
+    let path = config_path().context("Could not find home directory to save the config")?;
+    let mut config = match Config::load(&path) {
+        Err(e) if e.is_not_found() => Config::default(),
+        loaded => loaded?,
+    };
+    change(&mut config)?;
+    Ok(config.save(path)?)
+}
+
+fn execute(binary: &Path, limits: &Limits, memcheck: bool) -> Result<ExitCode> {
+    let (run, summary) = if memcheck {
+        let (run, summary) = memcheck::run(binary, Stdio::inherit(), false, limits)?;
+        (run, Some(summary))
+    } else {
+        let run = execute::run(&Invocation::new(binary), Stdio::inherit(), false, limits)?;
+        (run, None)
+    };
+    eprintln!(
+        "`{}` {} ({:.2}s)",
+        binary.display(),
+        run.verdict,
+        run.duration.as_secs_f64()
+    );
+
+    if let Some(summary) = summary.filter(|summary| !summary.is_clean()) {
+        eprintln!("memcheck: {}", summary.verdict());
+        for issue in &summary.issues {
+            eprintln!("  {issue}");
+        }
+        return Ok(ExitCode::FAILURE);
     }
+    Ok(match run.verdict {
+        Verdict::Exited(code) => ExitCode::from(code as u8),
+        _ => ExitCode::FAILURE,
+    })
+}
 
+fn run_pipe(config: &Config, name: &str, run: PipeRun, region: Option<&str>) -> Result<()> {
+    let projects = region::resolve(config, run.target.as_deref(), region)?;
+    // `{region}` is the region named on the command line, if any.
+    let region = run
+        .target
+        .as_deref()
+        .filter(|target| !Path::new(target).is_dir())
+        .or(region)
+        .filter(|name| {
+            config
+                .regions
+                .as_ref()
+                .is_some_and(|r| r.contains_key(*name))
+        });
+    let options = PipeOptions {
+        region: region.map(str::to_string),
+        case: run.case,
+        output: run.output,
+        dry_run: run.dry_run,
+    };
+    pipe::run(config, name, &projects, &options)
+}
+
+/// `home` is the config stored in `$HOME`. Commands use it with project-local overrides applied,
+/// while commands that change the config go through [`update_config`].
+fn run(cli: Cli, home: Config) -> Result<ExitCode> {
+    let config = match &cli.command {
+        // These only touch `~/.cvutie`, so a broken project `.cvutie` mustn't stand in their way.
+        Commands::Config {
+            action: ConfigAction::Init { .. } | ConfigAction::Edit | ConfigAction::Set { .. },
+        } => home.clone(),
+        _ => local_configuration(home.clone())?,
+    };
     match cli.command {
-        Commands::Init { bare } => {
-            println!("Initializing {}repository", if bare { "bare " } else { "" });
+        Commands::Compile {
+            target,
+            output,
+            profile,
+        } => {
+            let toolchain = compile::Toolchain::resolve(&config, profile.as_deref())?;
+            for dir in region::resolve(&config, target.as_deref(), cli.region.as_deref())? {
+                hooks::run(&config, Hook::PreCompile, &dir, &[])?;
+                let binary = compile::compile(&config, &toolchain, &dir, output.as_deref())?;
+                println!("Compiled `{}`", binary.display());
+                hooks::run_post_compile(&config, &dir, &binary);
+            }
+        }
+
+        Commands::Execute {
+            target,
+            time_limit,
+            cpu_time_limit,
+            memory_limit,
+            memcheck,
+        } => {
+            let mut limits = Limits::from_config(&config)?;
+            if let Some(secs) = time_limit {
+                limits.wall_time = Some(execute::wall_time(secs)?);
+            }
+            limits.cpu_time_secs = cpu_time_limit.or(limits.cpu_time_secs);
+            limits.memory_mb = memory_limit.or(limits.memory_mb);
+            if memcheck {
+                memcheck::ensure_available()?;
+            }
+
+            let binaries = match target.as_deref().map(Path::new) {
+                // `Command` would look a bare file name up in `$PATH`.
+                Some(binary) if binary.is_file() => vec![std::path::absolute(binary)?],
+                _ => region::resolve(&config, target.as_deref(), cli.region.as_deref())?
+                    .into_iter()
+                    .map(|dir| dir.join(&config.default_bin_output_name))
+                    .collect(),
+            };
+            // Every binary runs, but the first failure decides the exit code.
+            let mut code = ExitCode::SUCCESS;
+            for binary in binaries {
+                let run = execute(&binary, &limits, memcheck)?;
+                if code == ExitCode::SUCCESS {
+                    code = run;
+                }
+            }
+            return Ok(code);
         }
 
-        Commands::Status => {
-            println!("Checking working tree status");
+        Commands::Pipe {
+            action: Some(action),
+            ..
+        } => match action {
+            PipeAction::Save { name, steps, force } => {
+                update_config(|home| pipe::save(home, &name, steps, force))?;
+                println!("Pipe `{name}` saved");
+            }
+            PipeAction::List => pipe::list(&config),
+            PipeAction::Show { name } => pipe::show(&config, &name)?,
+            PipeAction::Delete { name } => {
+                update_config(|home| pipe::delete(home, &name))?;
+                println!("Pipe `{name}` deleted");
+            }
+            PipeAction::Run { name, run } => run_pipe(&config, &name, run, cli.region.as_deref())?,
+        },
+
+        Commands::Pipe { name, run, .. } => {
+            let name = name.context("`pipe` requires the name of a pipe")?;
+            run_pipe(&config, &name, run, cli.region.as_deref())?;
         }
 
-        Commands::Add { files, update, all } => {
-            if all {
-                println!("Adding all changes");
-            } else if update {
-                println!("Adding modified files");
-            } else {
-                println!("Adding files: {:?}", files);
+        Commands::TestAll {
+            target,
+            sanitize,
+            memcheck,
+            jobs,
+            report,
+            report_file,
+        } => {
+            let roots = region::resolve(&config, target.as_deref(), cli.region.as_deref())?;
+            let options = TestOptions {
+                jobs: jobs.unwrap_or_else(pool::default_jobs),
+                sanitize,
+                memcheck,
+            };
+            let results = testing::test_all(&config, &home, &roots, &options)?;
+            if let (Some(format), Some(path)) = (report, report_file) {
+                report::write(format, &path, &results.total, &results.cases)?;
+                println!("Wrote report to `{}`", path.display());
+            }
+            if results.total.failed > 0 {
+                return Ok(ExitCode::FAILURE);
             }
         }
 
-        Commands::Commit { message, amend } => {
-            if amend {
-                println!("Amending previous commit");
-            } else {
-                match message {
-                    Some(msg) => println!("Creating commit with message: {}", msg),
-                    None => println!("Opening editor for commit message"),
+        Commands::Region {
+            action: Some(action),
+            ..
+        } => match action {
+            RegionAction::List => region::list(&config),
+            RegionAction::Show { name } => region::show(&config, &name)?,
+            RegionAction::Remove { name, folders } => {
+                update_config(|home| region::remove(home, &name, &folders))?;
+                if folders.is_empty() {
+                    println!("Region `{name}` removed");
+                } else {
+                    println!("Region `{name}` updated");
                 }
             }
+            RegionAction::Rename { old, new } => {
+                update_config(|home| region::rename(home, &old, &new))?;
+                println!("Region `{old}` renamed to `{new}`");
+            }
+        },
+
+        Commands::Region {
+            action: None,
+            folders,
+            add,
+            force,
+        } => {
+            let region = cli.region.context("`region` requires `--region <name>`")?;
+            update_config(|home| region::set(home, &region, &folders, add, force))?;
+            println!("Region `{region}` saved");
+        }
+
+        Commands::New { name, local_config } => {
+            scaffold::new(&config, &name, local_config)?;
+            println!("Created project `{}`", name.display());
+            if let Some(region) = cli.region {
+                let folder = name.to_string_lossy().into_owned();
+                update_config(|home| region::set(home, &region, &[folder], true, false))?;
+                println!("Added `{}` to region `{region}`", name.display());
+            }
         }
 
-        Commands::Log {
-            number,
-            one_line,
-            stat,
+        Commands::Samples {
+            action:
+                SamplesAction::Import {
+                    archive,
+                    dir,
+                    force,
+                },
         } => {
-            println!(
-                "Showing {} commits{}{}",
-                number,
-                if one_line { " in one-line format" } else { "" },
-                if stat { " with stats" } else { "" }
-            );
+            let dir = match dir {
+                Some(dir) => dir,
+                None => std::env::current_dir()?,
+            };
+            samples::import(&config, &archive, &dir, force)?;
         }
 
-        Commands::Checkout { branch, new_branch } => {
-            if new_branch {
-                println!("Creating and checking out new branch: {}", branch);
-            } else {
-                println!("Checking out branch: {}", branch);
+        Commands::Config { action } => {
+            let path = config_path().context("Could not find home directory")?;
+            match action {
+                ConfigAction::Init { force } => {
+                    if path.exists() && !force {
+                        bail!(
+                            "`{}` already exists. Use `--force` to overwrite it",
+                            path.display()
+                        );
+                    }
+                    Config::default().save(&path)?;
+                    println!("Wrote default config to `{}`", path.display());
+                }
+                ConfigAction::Show => config_cmd::show(&config)?,
+                ConfigAction::Get { key } => config_cmd::get(&config, &key)?,
+                ConfigAction::Set { key, value, append } => {
+                    update_config(|home| {
+                        *home = config_cmd::set(home, &key, &value, append)?;
+                        Ok(())
+                    })?;
+                    println!("Updated `{key}` in `{}`", path.display());
+                }
+                ConfigAction::Edit => config_cmd::edit(&path)?,
             }
         }
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-        Commands::Branch {
-            name,
-            delete,
-            remotes,
-        } => match (name, delete, remotes) {
-            (Some(branch), true, _) => println!("Deleting branch: {}", branch),
-            (Some(branch), false, _) => println!("Creating branch: {}", branch),
-            (None, _, true) => println!("Listing remote branches"),
-            (None, _, false) => println!("Listing local branches"),
-        },
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // `config` commands manage the file themselves.
+    let config = get_configuration(!matches!(cli.command, Commands::Config { .. }));
 
-        Commands::Pull {
-            remote,
-            branch,
-            rebase,
-        } => {
-            println!(
-                "Pulling {} from {}/{}",
-                if rebase { "with rebase" } else { "with merge" },
-                remote,
-                branch
-            );
+    match run(cli, config) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::FAILURE
         }
     }
 }
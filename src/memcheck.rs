@@ -0,0 +1,298 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fmt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    execute::{self, Invocation, Limits, Run},
+    temp,
+};
+
+/// An error memcheck reported: an invalid access, a bad free, a leak record and so on.
+pub struct Issue {
+    /// E.g. `Invalid read of size 4`.
+    pub what: String,
+    pub location: Option<String>,
+    pub function: Option<String>,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.what)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {location}")?;
+        }
+        if let Some(function) = &self.function {
+            write!(f, " in {function}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What memcheck found in a single run.
+#[derive(Default)]
+pub struct Summary {
+    pub definitely_lost: u64,
+    pub indirectly_lost: u64,
+    /// Every reported error, including definite and indirect leak records.
+    pub issues: Vec<Issue>,
+}
+
+impl Summary {
+    pub fn leaked(&self) -> bool {
+        self.definitely_lost + self.indirectly_lost > 0
+    }
+
+    pub fn is_clean(&self) -> bool {
+        !self.leaked() && self.issues.is_empty()
+    }
+
+    /// One-line leak verdict, e.g. `40 bytes definitely lost, 0 bytes indirectly lost`.
+    pub fn verdict(&self) -> String {
+        if !self.leaked() {
+            return "no leaks".to_string();
+        }
+        format!(
+            "{} bytes definitely lost, {} bytes indirectly lost",
+            self.definitely_lost, self.indirectly_lost
+        )
+    }
+}
+
+/// Fails unless valgrind can be run.
+pub fn ensure_available() -> Result<()> {
+    let found = Command::new("valgrind")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !found {
+        bail!("`--memcheck` requires valgrind, which was not found in $PATH");
+    }
+    Ok(())
+}
+
+/// Contents of the first `<tag>...</tag>` in `xml`.
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    elements(xml, tag).next()
+}
+
+/// Contents of every top-level `<tag>...</tag>` in `xml`.
+fn elements<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let len = rest[start..].find(&close)?;
+        let content = &rest[start..start + len];
+        rest = &rest[start + len + close.len()..];
+        Some(content)
+    })
+}
+
+fn unescape(text: &str) -> String {
+    text.trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parses memcheck's `--xml=yes` output.
+pub fn parse(xml: &str) -> Summary {
+    let mut summary = Summary::default();
+
+    for error in elements(xml, "error") {
+        let kind = element(error, "kind").unwrap_or_default().trim();
+        let what = element(error, "what")
+            .or_else(|| element(error, "xwhat").and_then(|x| element(x, "text")))
+            .map(unescape)
+            .unwrap_or_else(|| kind.to_string());
+
+        let leaked_bytes = || {
+            element(error, "leakedbytes")
+                .and_then(|b| b.trim().parse::<u64>().ok())
+                .unwrap_or_default()
+        };
+        match kind {
+            "Leak_DefinitelyLost" => summary.definitely_lost += leaked_bytes(),
+            "Leak_IndirectlyLost" => summary.indirectly_lost += leaked_bytes(),
+            // Not counted against the program, but not worth listing either.
+            kind if kind.starts_with("Leak_") => continue,
+            _ => {}
+        }
+
+        // The innermost frame with debug info that isn't valgrind's own malloc replacement.
+        let frame = element(error, "stack").and_then(|stack| {
+            elements(stack, "frame").find(|frame| {
+                element(frame, "file").is_some_and(|file| !file.starts_with("vg_replace"))
+            })
+        });
+        let location = frame.and_then(|frame| {
+            let file = unescape(element(frame, "file")?);
+            Some(match element(frame, "line") {
+                Some(line) => format!("{file}:{}", line.trim()),
+                None => file,
+            })
+        });
+        summary.issues.push(Issue {
+            what,
+            location,
+            function: frame.and_then(|f| element(f, "fn")).map(unescape),
+        });
+    }
+    summary
+}
+
+/// Runs `binary` under memcheck, like [`execute::run`], and returns its findings alongside.
+/// Callers check [`ensure_available`] first.
+pub fn run(binary: &Path, stdin: Stdio, capture: bool, limits: &Limits) -> Result<(Run, Summary)> {
+    // Valgrind writes into the file we just created, not whatever was at the path before.
+    let (xml, _) = temp::file("memcheck", ".xml")?;
+
+    let mut invocation = Invocation::new("valgrind");
+    invocation.args = vec![
+        "--tool=memcheck".into(),
+        "--leak-check=full".into(),
+        "--xml=yes".into(),
+        format!("--xml-file={}", xml.display()).into(),
+        binary.into(),
+    ];
+    // Valgrind needs far more address space than the program it runs.
+    let limits = Limits {
+        memory_mb: None,
+        ..*limits
+    };
+
+    let run = execute::run(&invocation, stdin, capture, &limits);
+    let report = std::fs::read_to_string(&xml);
+    let _ = std::fs::remove_file(&xml);
+
+    let run = run?;
+    let report = report
+        .ok()
+        .filter(|report| !report.is_empty())
+        .context("valgrind did not produce an XML report")?;
+    Ok((run, parse(&report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `--xml=yes` output, in valgrind's protocol 4, for a program that reads past a `malloc`ed
+    /// array and leaks it.
+    const REPORT: &str = r#"<?xml version="1.0"?>
+<valgrindoutput>
+<protocolversion>4</protocolversion>
+<protocoltool>memcheck</protocoltool>
+<status>
+  <state>RUNNING</state>
+</status>
+<error>
+  <unique>0x0</unique>
+  <tid>1</tid>
+  <kind>InvalidRead</kind>
+  <what>Invalid read of size 4</what>
+  <stack>
+    <frame>
+      <ip>0x109189</ip>
+      <obj>/tmp/p/main</obj>
+      <fn>sum</fn>
+      <dir>/tmp/p</dir>
+      <file>main.c</file>
+      <line>3</line>
+    </frame>
+    <frame>
+      <ip>0x1091D2</ip>
+      <obj>/tmp/p/main</obj>
+      <fn>main</fn>
+      <dir>/tmp/p</dir>
+      <file>main.c</file>
+      <line>8</line>
+    </frame>
+  </stack>
+  <auxwhat>Address 0x4a8d050 is 0 bytes after a block of size 16 alloc'd</auxwhat>
+</error>
+<error>
+  <unique>0x1</unique>
+  <tid>1</tid>
+  <kind>Leak_DefinitelyLost</kind>
+  <xwhat>
+    <text>16 bytes in 1 blocks are definitely lost in loss record 1 of 2</text>
+    <leakedbytes>16</leakedbytes>
+    <leakedblocks>1</leakedblocks>
+  </xwhat>
+  <stack>
+    <frame>
+      <ip>0x48417B4</ip>
+      <obj>/usr/libexec/valgrind/vgpreload_memcheck-amd64-linux.so</obj>
+      <fn>malloc</fn>
+      <dir>./coregrind/m_replacemalloc</dir>
+      <file>vg_replace_malloc.c</file>
+      <line>381</line>
+    </frame>
+    <frame>
+      <ip>0x1091B5</ip>
+      <obj>/tmp/p/main</obj>
+      <fn>main</fn>
+      <dir>/tmp/p</dir>
+      <file>main.c</file>
+      <line>5</line>
+    </frame>
+  </stack>
+</error>
+<error>
+  <unique>0x2</unique>
+  <tid>1</tid>
+  <kind>Leak_StillReachable</kind>
+  <xwhat>
+    <text>8 bytes in 1 blocks are still reachable in loss record 2 of 2</text>
+    <leakedbytes>8</leakedbytes>
+    <leakedblocks>1</leakedblocks>
+  </xwhat>
+</error>
+<errorcounts>
+</errorcounts>
+</valgrindoutput>
+"#;
+
+    #[test]
+    fn counts_definite_leaks_only() {
+        let summary = parse(REPORT);
+        assert_eq!(summary.definitely_lost, 16);
+        assert_eq!(summary.indirectly_lost, 0);
+        assert_eq!(
+            summary.verdict(),
+            "16 bytes definitely lost, 0 bytes indirectly lost"
+        );
+        assert!(!summary.is_clean());
+    }
+
+    #[test]
+    fn lists_errors_at_their_user_frame() {
+        let issues: Vec<String> = parse(REPORT)
+            .issues
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                "Invalid read of size 4 at main.c:3 in sum",
+                "16 bytes in 1 blocks are definitely lost in loss record 1 of 2 at main.c:5 in main",
+            ]
+        );
+    }
+
+    #[test]
+    fn clean_run_has_no_issues() {
+        let summary = parse("<valgrindoutput>\n<errorcounts>\n</errorcounts>\n</valgrindoutput>\n");
+        assert!(summary.is_clean());
+        assert_eq!(summary.verdict(), "no leaks");
+    }
+}
@@ -0,0 +1,636 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    pool, region, style,
+    testing::{INPUT_SUFFIX, OUTPUT_SUFFIX},
+};
+
+/// Looks up pipe `name`, listing the known pipes if it doesn't exist.
+fn get<'a>(config: &'a Config, name: &str) -> Result<&'a [String]> {
+    if let Some(steps) = config.pipes.as_ref().and_then(|pipes| pipes.get(name)) {
+        return Ok(steps);
+    }
+    let mut known: Vec<&str> = config
+        .pipes
+        .iter()
+        .flat_map(|pipes| pipes.keys().map(String::as_str))
+        .collect();
+    known.sort_unstable();
+    if known.is_empty() {
+        bail!("Pipe `{name}` does not exist (no pipes are defined)")
+    }
+    bail!(
+        "Pipe `{name}` does not exist. Known pipes: {}",
+        known.join(", ")
+    )
+}
+
+/// Saves `steps` as pipe `name`, refusing to overwrite an existing pipe unless `force` is set.
+pub fn save(config: &mut Config, name: &str, steps: Vec<String>, force: bool) -> Result<()> {
+    let pipes = config.pipes.get_or_insert_with(Default::default);
+    if pipes.contains_key(name) && !force {
+        bail!("Pipe `{name}` already exists. Use `--force` to overwrite it");
+    }
+    pipes.insert(name.to_string(), steps);
+    Ok(())
+}
+
+/// Prints every pipe with the number of steps it has.
+pub fn list(config: &Config) {
+    let mut pipes: Vec<_> = config.pipes.iter().flat_map(|p| p.iter()).collect();
+    if pipes.is_empty() {
+        println!("No pipes defined");
+        return;
+    }
+    pipes.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, steps) in pipes {
+        println!("{name} ({} steps)", steps.len());
+    }
+}
+
+/// Prints the numbered steps of pipe `name`.
+pub fn show(config: &Config, name: &str) -> Result<()> {
+    for (index, step) in get(config, name)?.iter().enumerate() {
+        println!("{}. {step}", index + 1);
+    }
+    Ok(())
+}
+
+/// Removes pipe `name`.
+pub fn delete(config: &mut Config, name: &str) -> Result<()> {
+    get(config, name)?;
+    if let Some(pipes) = &mut config.pipes {
+        pipes.remove(name);
+    }
+    Ok(())
+}
+
+/// Quotes `text` for `sh`.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The shell command a step runs: a leading `cvutie` invokes this binary, and a leading `.py`
+/// script is run with `python3`. Anything else is run by `sh` as is.
+fn resolve_step(step: &str, exe: &Path) -> String {
+    let step = step.trim();
+    let (first, rest) = step.split_once(char::is_whitespace).unwrap_or((step, ""));
+    if first == "cvutie" {
+        format!("{} {rest}", quote(&exe.to_string_lossy()))
+            .trim_end()
+            .to_string()
+    } else if first.ends_with(".py") {
+        format!("python3 {step}")
+    } else {
+        step.to_string()
+    }
+}
+
+/// Quotes `value` for `sh` unless it only contains characters that are safe unquoted.
+fn shell_word(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./,:=+@%".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        value.to_string()
+    } else {
+        quote(value)
+    }
+}
+
+/// Whether a spot in a step lies inside quotes.
+#[derive(Clone, Copy, PartialEq)]
+enum Quoting {
+    Bare,
+    Single,
+    Double,
+}
+
+/// The quoting in effect at the end of `text`.
+fn quoting(text: &str) -> Quoting {
+    let mut quoting = Quoting::Bare;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        quoting = match (quoting, c) {
+            (Quoting::Bare, '\'') => Quoting::Single,
+            (Quoting::Bare, '"') => Quoting::Double,
+            (Quoting::Single, '\'') | (Quoting::Double, '"') => Quoting::Bare,
+            (Quoting::Bare | Quoting::Double, '\\') => {
+                chars.next();
+                quoting
+            }
+            (quoting, _) => quoting,
+        };
+    }
+    quoting
+}
+
+/// `value` escaped for a spot in a step quoted as `quoting`.
+/// Single-quoted text is taken to be code for another shell, as in `sh -c '...'`, so the value
+/// is quoted for that shell and then escaped for this one. Inside double quotes it is escaped
+/// to be taken literally.
+fn quote_in(value: &str, quoting: Quoting) -> String {
+    match quoting {
+        Quoting::Bare => shell_word(value),
+        Quoting::Single => shell_word(value).replace('\'', r"'\''"),
+        Quoting::Double => {
+            let mut out = String::with_capacity(value.len());
+            for c in value.chars() {
+                if "\"$`\\".contains(c) {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out
+        }
+    }
+}
+
+/// Options of a `pipe` run.
+pub struct PipeOptions {
+    /// Region named on the command line, for `{region}`.
+    pub region: Option<String>,
+    /// Test case for `{test_in}` and `{test_out}`, as `<id>` or `<folder>/<id>`.
+    pub case: Option<String>,
+    /// Where the output of the last step goes, instead of stdout.
+    pub output: Option<PathBuf>,
+    /// Print the commands instead of running them.
+    pub dry_run: bool,
+}
+
+/// Values of the placeholders in a step, for one project.
+struct Placeholders<'a> {
+    config: &'a Config,
+    options: &'a PipeOptions,
+    project: PathBuf,
+}
+
+impl Placeholders<'_> {
+    /// The input and output of `PipeOptions::case`, searched for in every test folder unless
+    /// it names one.
+    fn case(&self) -> Result<(PathBuf, PathBuf)> {
+        let Some(case) = &self.options.case else {
+            bail!("`{{test_in}}` and `{{test_out}}` require `--case <id>`");
+        };
+        let (folders, id) = match case.split_once('/') {
+            Some((folder, id)) => (vec![folder.to_string()], id),
+            None => (self.config.test_folder_names.clone(), case.as_str()),
+        };
+        folders
+            .iter()
+            .map(|folder| {
+                let folder = self.project.join(folder);
+                (
+                    folder.join(format!("{id}{INPUT_SUFFIX}")),
+                    folder.join(format!("{id}{OUTPUT_SUFFIX}")),
+                )
+            })
+            .find(|(input, _)| input.is_file())
+            .with_context(|| {
+                format!(
+                    "Test case `{case}` not found in `{}`",
+                    self.project.display()
+                )
+            })
+    }
+
+    /// The value of `{name}`: a placeholder, then an environment variable. `None` leaves the
+    /// braces alone, so shell and awk code in a step keeps working.
+    fn value(&self, name: &str) -> Result<Option<String>> {
+        let path = |path: PathBuf| Some(path.display().to_string());
+        Ok(match name {
+            "region" => {
+                let region = self.options.region.as_deref();
+                let region = region.or_else(|| region::containing(self.config, &self.project));
+                Some(
+                    region
+                        .with_context(|| {
+                            format!(
+                                "`{{region}}`: `{}` is not part of any region",
+                                self.project.display()
+                            )
+                        })?
+                        .to_string(),
+                )
+            }
+            "project" => path(self.project.clone()),
+            "binary" => path(self.project.join(&self.config.default_bin_output_name)),
+            "source" => {
+                let sources = &self.config.source_code_filenames;
+                let source = sources
+                    .iter()
+                    .map(|name| self.project.join(name))
+                    .find(|path| path.is_file());
+                path(source.with_context(|| {
+                    format!(
+                        "`{{source}}`: no source files ({}) found in `{}`",
+                        sources.join(", "),
+                        self.project.display()
+                    )
+                })?)
+            }
+            "test_in" => path(self.case()?.0),
+            "test_out" => path(self.case()?.1),
+            name => std::env::var(name).ok(),
+        })
+    }
+
+    /// Replaces every `{name}` in `step` that has a value, quoted as [`quote_in`] describes.
+    /// `${name}` is left to the shell.
+    fn expand(&self, step: &str) -> Result<String> {
+        let mut out = String::with_capacity(step.len());
+        let mut rest = step;
+        while let Some(start) = rest.find('{') {
+            let (before, after) = rest.split_at(start);
+            out.push_str(before);
+            let name = after[1..].find('}').map(|end| &after[1..end + 1]);
+            let is_name = |name: &&str| {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            };
+            match name.filter(is_name).filter(|_| !before.ends_with('$')) {
+                Some(name) => {
+                    match self.value(name)? {
+                        Some(value) => {
+                            let quoting = quoting(&step[..step.len() - after.len()]);
+                            out.push_str(&quote_in(&value, quoting));
+                        }
+                        None => out.push_str(&after[..name.len() + 2]),
+                    }
+                    rest = &after[name.len() + 2..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &after[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Runs `command` in `dir` with `input` as its stdin, or the terminal for the first step, and
+/// returns its stdout.
+fn run_step(
+    command: &str,
+    dir: &Path,
+    input: Option<&[u8]>,
+) -> Result<(std::process::ExitStatus, Vec<u8>)> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(input.map_or_else(Stdio::inherit, |_| Stdio::piped()))
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run `{command}`"))?;
+
+    // Feed stdin from another thread, so a step that writes before reading everything can't
+    // deadlock against us.
+    let stdin = child.stdin.take();
+    let output = std::thread::scope(|scope| {
+        if let (Some(mut stdin), Some(input)) = (stdin, input) {
+            // A step that exits without reading its input is not an error.
+            scope.spawn(move || stdin.write_all(input));
+        }
+        child.wait_with_output()
+    })?;
+    Ok((output.status, output.stdout))
+}
+
+/// Marks a step that runs once per project instead of once per pipe run.
+const EACH: &str = "@each";
+/// Like [`EACH`], but runs the projects in parallel.
+const PARALLEL: &str = "@parallel";
+
+/// How often a step runs.
+#[derive(Clone, Copy, PartialEq)]
+enum FanOut {
+    Once,
+    Each,
+    Parallel,
+}
+
+/// Splits the fan-out modifier off `step`.
+fn fan_out(step: &str) -> (FanOut, &str) {
+    let step = step.trim_start();
+    let (first, rest) = step.split_once(char::is_whitespace).unwrap_or((step, ""));
+    match first {
+        EACH => (FanOut::Each, rest.trim_start()),
+        PARALLEL => (FanOut::Parallel, rest.trim_start()),
+        _ => (FanOut::Once, step),
+    }
+}
+
+/// How one project did in a fanned out step.
+struct Row {
+    status: std::process::ExitStatus,
+    duration: Duration,
+    output: Vec<u8>,
+}
+
+/// The number of a fanned out step and a row per project, `None` for the projects skipped after
+/// failing an earlier step.
+type Column = (usize, Vec<Option<Row>>);
+
+/// Renders a line per project with how it did in every fanned out step and the last line of
+/// output of the last step it ran.
+fn table(projects: &[PathBuf], columns: &[Column]) -> String {
+    let mut header = vec!["PROJECT".to_string()];
+    header.extend(columns.iter().map(|(number, _)| format!("STEP {number}")));
+    header.push("OUTPUT".to_string());
+
+    let mut lines = vec![header];
+    for (index, project) in projects.iter().enumerate() {
+        let mut line = vec![project.display().to_string()];
+        let mut last_output = String::new();
+        for (_, rows) in columns {
+            let Some(row) = &rows[index] else {
+                line.push("skipped".to_string());
+                continue;
+            };
+            let output = String::from_utf8_lossy(&row.output);
+            last_output = output.lines().last().unwrap_or_default().trim().to_string();
+            let seconds = row.duration.as_secs_f64();
+            line.push(if row.status.success() {
+                format!("ok {seconds:.2}s")
+            } else {
+                format!("failed ({}) {seconds:.2}s", row.status)
+            });
+        }
+        line.push(last_output);
+        lines.push(line);
+    }
+
+    let mut widths = vec![0; lines[0].len()];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for line in &lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Everything a run of one pipe needs.
+struct Runner<'a> {
+    config: &'a Config,
+    name: &'a str,
+    steps: Vec<(FanOut, &'a str)>,
+    /// Projects that fanned out steps run for.
+    projects: &'a [PathBuf],
+    options: &'a PipeOptions,
+    exe: PathBuf,
+}
+
+impl Runner<'_> {
+    /// The command of `step` for the project in `dir`.
+    fn command(&self, step: &str, dir: &Path) -> Result<String> {
+        let placeholders = Placeholders {
+            config: self.config,
+            options: self.options,
+            project: std::path::absolute(dir)?,
+        };
+        Ok(resolve_step(&placeholders.expand(step)?, &self.exe))
+    }
+
+    /// Runs the command of every project that has one, one at a time or in parallel.
+    fn fan_out(
+        &self,
+        commands: &[Option<(PathBuf, String)>],
+        parallel: bool,
+        input: &[u8],
+    ) -> Result<Vec<Option<Row>>> {
+        // Steps mostly wait on child processes, so parallel ones start every project at once.
+        let jobs = if parallel { commands.len() } else { 1 };
+        pool::map(commands, jobs, |command| {
+            let Some((dir, command)) = command else {
+                return Ok(None);
+            };
+            let start = Instant::now();
+            let (status, output) = run_step(command, dir, Some(input))?;
+            Ok(Some(Row {
+                status,
+                duration: start.elapsed(),
+                output,
+            }))
+        })
+        .into_iter()
+        .collect()
+    }
+
+    /// Runs every step in `dir`, feeding each step's stdout to the next, and returns the output
+    /// of the last one. Fanned out steps add a [`Column`] to `columns`, and their output is that
+    /// of every project that succeeded.
+    fn run(&self, dir: &Path, columns: &mut Vec<Column>) -> Result<Vec<u8>> {
+        let mut data: Option<Vec<u8>> = None;
+        // Projects that haven't failed a fanned out step yet.
+        let mut alive = vec![true; self.projects.len()];
+
+        for (index, &(fan_out, step)) in self.steps.iter().enumerate() {
+            let number = index + 1;
+            if fan_out == FanOut::Once {
+                let command = self.command(step, dir)?;
+                if self.options.dry_run {
+                    println!("{number}. {command}");
+                    continue;
+                }
+                let (status, stdout) = run_step(&command, dir, data.as_deref())?;
+                if !status.success() {
+                    bail!(
+                        "Step {number} of pipe `{}` (`{step}`) failed in `{}` ({status})",
+                        self.name,
+                        dir.display()
+                    );
+                }
+                data = Some(stdout);
+                continue;
+            }
+
+            let commands = self
+                .projects
+                .iter()
+                .zip(&alive)
+                .map(|(project, &alive)| {
+                    alive
+                        .then(|| Ok((project.clone(), self.command(step, project)?)))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if self.options.dry_run {
+                for (project, command) in commands.iter().flatten() {
+                    println!("{number}. [{}] {command}", project.display());
+                }
+                continue;
+            }
+
+            let input = data.take().unwrap_or_default();
+            let rows = self.fan_out(&commands, fan_out == FanOut::Parallel, &input)?;
+            let mut output = Vec::new();
+            for (alive, row) in alive.iter_mut().zip(&rows) {
+                match row {
+                    Some(row) if row.status.success() => output.extend_from_slice(&row.output),
+                    Some(_) => *alive = false,
+                    None => {}
+                }
+            }
+            data = Some(output);
+            columns.push((number, rows));
+        }
+
+        Ok(data.unwrap_or_default())
+    }
+}
+
+/// Runs pipe `name` once for every project in `projects`, in the project's directory. Each step's
+/// stdout feeds the next step, and the output of the last step goes to `PipeOptions::output`, or
+/// stdout. Stops at the first failing step.
+///
+/// A pipe with an `@each` or `@parallel` step instead runs once, in the current directory, and
+/// those steps run once per project, one at a time or in parallel. A project that fails such a
+/// step is skipped by the later ones while the others keep going, so the output only covers the
+/// projects that passed, and the pipe fails once it is written. A table of how each project did in
+/// every such step is printed to stderr at the end.
+pub fn run(config: &Config, name: &str, projects: &[PathBuf], options: &PipeOptions) -> Result<()> {
+    let runner = Runner {
+        config,
+        name,
+        steps: get(config, name)?
+            .iter()
+            .map(|step| fan_out(step))
+            .collect(),
+        projects,
+        options,
+        exe: std::env::current_exe().context("Could not locate the cvutie binary")?,
+    };
+
+    let mut result = Vec::new();
+    let mut columns = Vec::new();
+    if runner
+        .steps
+        .iter()
+        .any(|&(fan_out, _)| fan_out != FanOut::Once)
+    {
+        let outcome = runner.run(&std::env::current_dir()?, &mut columns);
+        if !columns.is_empty() {
+            eprint!("{}", table(projects, &columns));
+        }
+        result = outcome?;
+    } else {
+        for dir in projects {
+            if projects.len() > 1 {
+                eprintln!("{}", style::bold(&format!("==> {}", dir.display())));
+            }
+            result.extend(runner.run(dir, &mut Vec::new())?);
+        }
+    }
+
+    match &options.output {
+        Some(path) if options.dry_run => println!("Output goes to `{}`", path.display()),
+        None if options.dry_run => {}
+        Some(path) => std::fs::write(path, result)
+            .with_context(|| format!("Could not write `{}`", path.display()))?,
+        None => std::io::stdout().write_all(&result)?,
+    }
+
+    let failed = (0..projects.len())
+        .filter(|&index| {
+            columns.iter().any(|(_, rows)| {
+                rows[index]
+                    .as_ref()
+                    .is_some_and(|row| !row.status.success())
+            })
+        })
+        .count();
+    if failed > 0 {
+        bail!(
+            "Pipe `{name}` failed in {failed} of {} projects",
+            projects.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(step: &str, project: &str) -> String {
+        let options = PipeOptions {
+            region: Some("hw".to_string()),
+            case: None,
+            output: None,
+            dry_run: false,
+        };
+        let placeholders = Placeholders {
+            config: &Config::default(),
+            options: &options,
+            project: PathBuf::from(project),
+        };
+        placeholders.expand(step).unwrap()
+    }
+
+    /// What `sh` prints for `step`.
+    fn run(step: &str) -> String {
+        let output = Command::new("sh").arg("-c").arg(step).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        assert_eq!(
+            expand("gcc {project}/main.c -o {binary} # {region}", "/p"),
+            "gcc /p/main.c -o /p/out # hw"
+        );
+    }
+
+    #[test]
+    fn leaves_shell_and_awk_braces_alone() {
+        for step in [
+            "echo ${HOME} {} {no_such_placeholder}",
+            "awk '{print $1}' | sed 's/{2,}/x/'",
+            "for f in {a,b}; do echo \"${f}\"; done",
+        ] {
+            assert_eq!(expand(step, "/p"), step);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_environment_variables() {
+        std::env::set_var("CVUTIE_PIPE_TEST", "from env");
+        assert_eq!(expand("echo {CVUTIE_PIPE_TEST}", "/p"), "echo 'from env'");
+    }
+
+    #[test]
+    fn quotes_values_for_the_shell() {
+        assert_eq!(shell_word("/tmp/p-1/main.c"), "/tmp/p-1/main.c");
+        assert_eq!(shell_word(""), "''");
+        assert_eq!(shell_word("it's here"), r"'it'\''s here'");
+
+        let project = "/tmp/it's a $HOME `x`";
+        assert_eq!(run(&expand("printf %s {project}", project)), project);
+        assert_eq!(run(&expand("printf %s \"{project}\"", project)), project);
+        assert_eq!(
+            run(&expand("sh -c 'printf %s {project}'", project)),
+            project
+        );
+        assert_eq!(
+            run(&expand("printf '%s|' {project} 'x' \"{project}\"", project)),
+            format!("{project}|x|{project}|")
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Resolves the project directories to operate on: `target` as an explicit path, then `target` or
+/// `region` as a region name, then the current directory.
+pub fn resolve(
+    config: &Config,
+    target: Option<&str>,
+    region: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    if let Some(path) = target.map(Path::new).filter(|path| path.is_dir()) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let Some(name) = target.or(region) else {
+        return Ok(vec![std::env::current_dir()?]);
+    };
+    if let Some(folders) = config.regions.as_ref().and_then(|r| r.get(name)) {
+        return Ok(folders.clone());
+    }
+
+    let mut known: Vec<&str> = config
+        .regions
+        .iter()
+        .flat_map(|r| r.keys().map(String::as_str))
+        .collect();
+    known.sort_unstable();
+    if known.is_empty() {
+        bail!("`{name}` is neither a directory nor a known region (no regions are defined)")
+    }
+    bail!(
+        "`{name}` is neither a directory nor a known region. Known regions: {}",
+        known.join(", ")
+    )
+}
+
+/// Overwrites `region` with `folders`, or extends it when `add` is set.
+pub fn set(
+    config: &mut Config,
+    region: &str,
+    folders: &[String],
+    add: bool,
+    force: bool,
+) -> Result<()> {
+    let folders = folders
+        .iter()
+        .map(|folder| {
+            let path = std::fs::canonicalize(folder)
+                .with_context(|| format!("Could not resolve directory `{folder}`"))?;
+            if !path.is_dir() {
+                bail!("`{folder}` is not a directory");
+            }
+            Ok(path)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let regions = config.regions.get_or_insert_with(Default::default);
+    match regions.get_mut(region) {
+        Some(existing) if add => {
+            for folder in folders {
+                if !existing.contains(&folder) {
+                    existing.push(folder);
+                }
+            }
+        }
+        Some(_) if !force => {
+            bail!("Region `{region}` already exists. Use `--add` to extend it or `--force` to overwrite it")
+        }
+        _ => {
+            regions.insert(region.to_string(), folders);
+        }
+    }
+    Ok(())
+}
+
+/// The region `dir` belongs to, i.e. the first one by name with a folder containing `dir`.
+pub fn containing<'a>(config: &'a Config, dir: &Path) -> Option<&'a str> {
+    let dir = std::fs::canonicalize(dir).ok()?;
+    config
+        .regions
+        .iter()
+        .flat_map(|r| r.iter())
+        .filter(|(_, folders)| folders.iter().any(|folder| dir.starts_with(folder)))
+        .map(|(name, _)| name.as_str())
+        .min()
+}
+
+fn get<'a>(config: &'a Config, region: &str) -> Result<&'a Vec<PathBuf>> {
+    config
+        .regions
+        .as_ref()
+        .and_then(|regions| regions.get(region))
+        .with_context(|| format!("Region `{region}` does not exist"))
+}
+
+/// Prints every region with the number of folders it contains.
+pub fn list(config: &Config) {
+    let mut names: Vec<_> = config.regions.iter().flat_map(|r| r.iter()).collect();
+    if names.is_empty() {
+        println!("No regions defined");
+        return;
+    }
+    names.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, folders) in names {
+        println!("{name} ({} folders)", folders.len());
+    }
+}
+
+/// Prints the folders of `region`, flagging the ones that no longer exist.
+pub fn show(config: &Config, region: &str) -> Result<()> {
+    for folder in get(config, region)? {
+        if folder.is_dir() {
+            println!("{}", folder.display());
+        } else {
+            println!("{} (missing)", folder.display());
+        }
+    }
+    Ok(())
+}
+
+/// Removes `folders` from `region`, or the whole region when `folders` is empty.
+pub fn remove(config: &mut Config, region: &str, folders: &[String]) -> Result<()> {
+    get(config, region)?;
+    let regions = config.regions.get_or_insert_with(Default::default);
+    if folders.is_empty() {
+        regions.remove(region);
+        return Ok(());
+    }
+
+    let existing = regions.entry(region.to_string()).or_default();
+    for folder in folders {
+        // Folders are stored canonicalized, but a removed directory can't be canonicalized anymore.
+        let path = std::fs::canonicalize(folder).unwrap_or_else(|_| PathBuf::from(folder));
+        let before = existing.len();
+        existing.retain(|f| f != &path);
+        if existing.len() == before {
+            bail!("`{folder}` is not part of region `{region}`");
+        }
+    }
+    Ok(())
+}
+
+/// Renames region `old` to `new`, refusing to overwrite an existing region.
+pub fn rename(config: &mut Config, old: &str, new: &str) -> Result<()> {
+    get(config, old)?;
+    let regions = config.regions.get_or_insert_with(Default::default);
+    if regions.contains_key(new) {
+        bail!("Region `{new}` already exists");
+    }
+    if let Some(folders) = regions.remove(old) {
+        regions.insert(new.to_string(), folders);
+    }
+    Ok(())
+}
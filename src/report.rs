@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{fmt::Write as _, path::Path};
+
+use crate::{
+    diff,
+    execute::Verdict,
+    style,
+    testing::{CaseRun, Outcome, Tally},
+};
+
+/// Diffs longer than this are cut off in reports.
+const MAX_DIFF_BYTES: usize = 4096;
+
+/// Format of a `test-all --report` file.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Junit,
+}
+
+/// The result of one case as written to a report.
+#[derive(Serialize)]
+pub struct CaseReport {
+    pub project: String,
+    /// The test folder, e.g. `CZE`.
+    pub folder: String,
+    pub case: String,
+    /// One of `pass`, `fail`, `timeout`, `crash`, `sanitizer`, `memcheck`, `error` or
+    /// `compile_error`.
+    pub verdict: &'static str,
+    pub duration_ms: u64,
+    /// How the program ended, e.g. `exited with code 0`. Missing if it never ran.
+    pub exit_status: Option<String>,
+    pub message: Option<String>,
+    /// Uncolored diff of expected against actual output, for `fail` only.
+    pub diff: Option<String>,
+}
+
+impl CaseReport {
+    pub fn new(project: &Path, folder: &str, case: &str, run: &Result<CaseRun>) -> Self {
+        let mut report = Self {
+            project: project.display().to_string(),
+            folder: folder.to_string(),
+            case: case.to_string(),
+            verdict: "error",
+            duration_ms: 0,
+            exit_status: None,
+            message: None,
+            diff: None,
+        };
+        let run = match run {
+            Ok(run) => run,
+            Err(e) => {
+                report.message = Some(format!("{e:#}"));
+                return report;
+            }
+        };
+
+        report.duration_ms = run.duration.as_millis() as u64;
+        report.exit_status = Some(run.verdict.to_string());
+        report.verdict = match &run.outcome {
+            Outcome::Passed => "pass",
+            Outcome::WrongOutput { expected, actual } => {
+                let (expected, actual) = (
+                    String::from_utf8_lossy(expected),
+                    String::from_utf8_lossy(actual),
+                );
+                report.message = Some(match diff::first_difference(&expected, &actual) {
+                    Some((line, column)) => {
+                        format!("output differs at line {line}, column {column}")
+                    }
+                    None => "output differs".to_string(),
+                });
+                report.diff = Some(truncate(style::strip(&diff::unified(&expected, &actual))));
+                "fail"
+            }
+            Outcome::Crashed(verdict) => {
+                report.message = Some(verdict.to_string());
+                match verdict {
+                    Verdict::WallTimeout(_) | Verdict::CpuTimeout(_) => "timeout",
+                    _ => "crash",
+                }
+            }
+            Outcome::Sanitizer(reports) => {
+                let lines: Vec<String> = reports.iter().map(ToString::to_string).collect();
+                report.message = Some(lines.join("\n"));
+                "sanitizer"
+            }
+            Outcome::Rejected(message) => {
+                report.message = Some(message.clone());
+                "fail"
+            }
+            Outcome::Memcheck(summary) => {
+                let mut message = summary.verdict();
+                for issue in &summary.issues {
+                    let _ = write!(message, "\n{issue}");
+                }
+                report.message = Some(message);
+                "memcheck"
+            }
+        };
+        report
+    }
+
+    /// A case that never ran because its project failed to compile.
+    pub fn compile_error(project: &Path, folder: &str, case: &str, error: &anyhow::Error) -> Self {
+        Self {
+            project: project.display().to_string(),
+            folder: folder.to_string(),
+            case: case.to_string(),
+            verdict: "compile_error",
+            duration_ms: 0,
+            exit_status: None,
+            message: Some(format!("{error:#}")),
+            diff: None,
+        }
+    }
+
+    fn passed(&self) -> bool {
+        self.verdict == "pass"
+    }
+
+    /// Whether the case couldn't be judged at all, as opposed to failing.
+    fn errored(&self) -> bool {
+        matches!(self.verdict, "error" | "compile_error")
+    }
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_DIFF_BYTES {
+        let mut end = MAX_DIFF_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n... truncated");
+    }
+    text
+}
+
+/// Writes `cases` to `path` in `format`.
+pub fn write(format: Format, path: &Path, total: &Tally, cases: &[CaseReport]) -> Result<()> {
+    let contents = match format {
+        Format::Json => {
+            #[derive(Serialize)]
+            struct Report<'a> {
+                passed: usize,
+                failed: usize,
+                cases: &'a [CaseReport],
+            }
+            let report = Report {
+                passed: total.passed,
+                failed: total.failed,
+                cases,
+            };
+            serde_json::to_string_pretty(&report)? + "\n"
+        }
+        Format::Junit => junit(cases),
+    };
+    std::fs::write(path, contents)
+        .with_context(|| format!("Could not write report to `{}`", path.display()))
+}
+
+/// Escapes `text` for use in XML content and attributes, dropping characters XML can't carry.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+/// Renders a JUnit XML report with one `<testsuite>` per project and test folder.
+fn junit(cases: &[CaseReport]) -> String {
+    let count =
+        |cases: &[CaseReport], f: fn(&CaseReport) -> bool| cases.iter().filter(|c| f(c)).count();
+    let failures = |c: &CaseReport| !c.passed() && !c.errored();
+    let time = |cases: &[CaseReport]| seconds(cases.iter().map(|c| c.duration_ms).sum());
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites name=\"cvutie\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">",
+        cases.len(),
+        count(cases, failures),
+        count(cases, CaseReport::errored),
+        time(cases)
+    );
+
+    for suite in cases.chunk_by(|a, b| a.project == b.project && a.folder == b.folder) {
+        let name = format!("{}/{}", suite[0].project, suite[0].folder);
+        let _ = writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">",
+            escape(&name),
+            suite.len(),
+            count(suite, failures),
+            count(suite, CaseReport::errored),
+            time(suite)
+        );
+        for case in suite {
+            let _ = write!(
+                out,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                escape(&case.case),
+                escape(&name),
+                seconds(case.duration_ms)
+            );
+            if case.passed() {
+                out.push_str("/>\n");
+                continue;
+            }
+
+            let tag = if case.errored() { "error" } else { "failure" };
+            let message = case.message.as_deref().unwrap_or_default();
+            let body = case
+                .diff
+                .as_deref()
+                .or(case.message.as_deref())
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                ">\n      <{tag} type=\"{}\" message=\"{}\">{}</{tag}>\n    </testcase>",
+                case.verdict,
+                escape(message.lines().next().unwrap_or_default()),
+                escape(body)
+            );
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
@@ -0,0 +1,190 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    config::Config,
+    temp,
+    testing::{read_dir_sorted, INPUT_SUFFIX, OUTPUT_SUFFIX},
+};
+
+/// Unpacks `archive` into `into` with `tar` or `unzip`, depending on its extension.
+fn unpack(archive: &Path, into: &Path) -> Result<()> {
+    let name = archive
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let mut command = if name.ends_with(".zip") {
+        let mut command = Command::new("unzip");
+        command.arg("-q").arg(archive).arg("-d").arg(into);
+        command
+    } else if [".tgz", ".tar.gz", ".tar"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+    {
+        let mut command = Command::new("tar");
+        command.arg("-xf").arg(archive).arg("-C").arg(into);
+        command
+    } else {
+        bail!(
+            "`{}` is not a .tgz, .tar.gz, .tar or .zip archive",
+            archive.display()
+        );
+    };
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run `{program}`"))?;
+    if !status.success() {
+        bail!(
+            "`{program}` could not unpack `{}` ({status})",
+            archive.display()
+        );
+    }
+    Ok(())
+}
+
+/// Every directory below `dir` named like one of `Config::test_folder_names`.
+fn find_test_folders(config: &Config, dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in read_dir_sorted(dir)? {
+        let Some(name) = entry.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        // Archives packed on macOS carry resource forks next to the real files.
+        if !entry.is_dir() || name == "__MACOSX" {
+            continue;
+        }
+        if config.test_folder_names.iter().any(|folder| folder == name) {
+            found.push(entry);
+        } else {
+            find_test_folders(config, &entry, found)?;
+        }
+    }
+    Ok(())
+}
+
+/// Inputs in `folder` without a matching output.
+fn unmatched_inputs(folder: &Path) -> Result<Vec<String>> {
+    let mut unmatched = Vec::new();
+    for file in read_dir_sorted(folder)? {
+        let Some(name) = file.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if let Some(id) = name.strip_suffix(INPUT_SUFFIX) {
+            if !folder.join(format!("{id}{OUTPUT_SUFFIX}")).is_file() {
+                unmatched.push(name.to_string());
+            }
+        }
+    }
+    Ok(unmatched)
+}
+
+/// Turns CRLF line endings into LF. Works on bytes, since test files aren't always UTF-8.
+fn normalize_line_endings(contents: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(contents.len());
+    for (i, &byte) in contents.iter().enumerate() {
+        if byte != b'\r' || contents.get(i + 1) != Some(&b'\n') {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+/// Copies the files of `from` into `to` with CRLF line endings turned into LF. Returns the number
+/// of test cases copied.
+fn copy_folder(from: &Path, to: &Path, force: bool) -> Result<usize> {
+    std::fs::create_dir_all(to).with_context(|| format!("Could not create `{}`", to.display()))?;
+
+    let mut cases = 0;
+    for file in read_dir_sorted(from)? {
+        let Some(name) = file.file_name().filter(|_| file.is_file()) else {
+            continue;
+        };
+        let contents =
+            std::fs::read(&file).with_context(|| format!("Could not read `{}`", file.display()))?;
+        let contents = normalize_line_endings(&contents);
+
+        let target = to.join(name);
+        if !force && target.is_file() && std::fs::read(&target)? != contents {
+            bail!(
+                "`{}` already exists with different contents. Use `--force` to overwrite it",
+                target.display()
+            );
+        }
+        std::fs::write(&target, contents)
+            .with_context(|| format!("Could not write `{}`", target.display()))?;
+        if name.to_string_lossy().ends_with(INPUT_SUFFIX) {
+            cases += 1;
+        }
+    }
+    Ok(cases)
+}
+
+/// Unpacks a Progtest sample archive into the test folders of the project in `dir`. Nothing is
+/// written unless every input in the archive has a matching output.
+pub fn import(config: &Config, archive: &Path, dir: &Path, force: bool) -> Result<()> {
+    if !archive.is_file() {
+        bail!("Archive `{}` does not exist", archive.display());
+    }
+    let unpacked = temp::dir("samples")?;
+
+    let result = (|| {
+        unpack(archive, &unpacked)?;
+
+        let mut folders = Vec::new();
+        find_test_folders(config, &unpacked, &mut folders)?;
+        if folders.is_empty() {
+            bail!(
+                "No test folders ({}) found in `{}`",
+                config.test_folder_names.join(", "),
+                archive.display()
+            );
+        }
+
+        let mut unmatched = Vec::new();
+        for folder in &folders {
+            let name = folder.file_name().unwrap_or_default().to_string_lossy();
+            unmatched.extend(
+                unmatched_inputs(folder)?
+                    .into_iter()
+                    .map(|input| format!("{name}/{input}")),
+            );
+        }
+        if !unmatched.is_empty() {
+            bail!(
+                "Inputs without a matching `{OUTPUT_SUFFIX}` file: {}",
+                unmatched.join(", ")
+            );
+        }
+
+        for folder in &folders {
+            let target = dir.join(folder.file_name().unwrap_or_default());
+            let cases = copy_folder(folder, &target, force)?;
+            println!("Imported {cases} cases into `{}`", target.display());
+        }
+        Ok(())
+    })();
+
+    let _ = std::fs::remove_dir_all(&unpacked);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_crlf_only() {
+        assert_eq!(normalize_line_endings(b"1\r\n2\r\n"), b"1\n2\n");
+        assert_eq!(normalize_line_endings(b"a\rb\r"), b"a\rb\r");
+    }
+
+    #[test]
+    fn keeps_bytes_that_are_not_utf8() {
+        assert_eq!(normalize_line_endings(b"V\xfdsledek\r\n"), b"V\xfdsledek\n");
+    }
+}
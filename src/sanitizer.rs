@@ -0,0 +1,185 @@
+use std::{fmt, path::Path};
+
+/// Flags appended to the default toolchain by `test-all --sanitize`.
+pub const FLAGS: &[&str] = &[
+    "-fsanitize=address,undefined",
+    "-fno-omit-frame-pointer",
+    "-g",
+];
+
+/// Environment for sanitized runs; UBSan only prints a stack trace when asked to.
+pub const ENV: &[(&str, &str)] = &[
+    ("ASAN_OPTIONS", "detect_leaks=1"),
+    ("UBSAN_OPTIONS", "print_stacktrace=1"),
+];
+
+/// A condensed AddressSanitizer, LeakSanitizer or UBSan report.
+pub struct Report {
+    /// E.g. `heap-buffer-overflow` or `runtime error: signed integer overflow`.
+    pub kind: String,
+    /// `file:line` of the innermost frame in user code.
+    pub location: Option<String>,
+    /// Function of that frame.
+    pub function: Option<String>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {location}")?;
+        }
+        if let Some(function) = &self.function {
+            write!(f, " in {function}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Frames inside the sanitizer runtime or libc rather than the program itself.
+fn is_runtime_frame(function: &str, location: &str) -> bool {
+    function.starts_with("__")
+        || ["malloc", "calloc", "realloc", "free"].contains(&function)
+        || ["sanitizer", "libasan", "libubsan", "libc.so", "libc-"]
+            .iter()
+            .any(|needle| location.contains(needle))
+}
+
+/// Shortens `/abs/path/main.c:12:5` to `main.c:12`.
+fn short_location(location: &str) -> String {
+    let mut parts = location.split(':');
+    let file = parts.next().unwrap_or(location);
+    let file = Path::new(file).file_name().map_or_else(
+        || file.to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    match parts.next().filter(|line| line.parse::<u32>().is_ok()) {
+        Some(line) => format!("{file}:{line}"),
+        None => file,
+    }
+}
+
+/// Parses a stack frame such as `#0 0x4011d6 in main /tmp/p/main.c:7:12`.
+fn parse_frame(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    let (_, rest) = rest.split_once(" in ")?;
+    let (function, location) = rest.split_once(' ')?;
+    Some((function, location.trim()))
+}
+
+/// Extracts every sanitizer report from a program's stderr.
+pub fn parse(stderr: &str) -> Vec<Report> {
+    let mut reports: Vec<Report> = Vec::new();
+    // Whether the last report still waits for its first user frame.
+    let mut open = false;
+
+    for line in stderr.lines() {
+        if let Some((_, rest)) = line.split_once("ERROR: AddressSanitizer: ") {
+            let kind = rest.split(" on ").next().unwrap_or(rest);
+            let kind = kind.split(" (").next().unwrap_or(kind);
+            reports.push(Report {
+                kind: kind.trim().to_string(),
+                location: None,
+                function: None,
+            });
+            open = true;
+        } else if line.contains("ERROR: LeakSanitizer: detected memory leaks") {
+            reports.push(Report {
+                kind: "memory leak".to_string(),
+                location: None,
+                function: None,
+            });
+            open = true;
+        } else if let Some((location, error)) = line.split_once(": runtime error: ") {
+            let error = error.split(':').next().unwrap_or(error);
+            reports.push(Report {
+                kind: format!("runtime error: {error}"),
+                location: Some(short_location(location)),
+                function: None,
+            });
+            open = true;
+        } else if let Some((function, location)) = parse_frame(line).filter(|_| open) {
+            if is_runtime_frame(function, location) {
+                continue;
+            }
+            if let Some(report) = reports.last_mut() {
+                report
+                    .location
+                    .get_or_insert_with(|| short_location(location));
+                report.function = Some(function.to_string());
+            }
+            open = false;
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heap_buffer_overflow_points_at_user_code() {
+        let stderr = "\
+=================================================================
+==23577==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000020 at pc 0x5636e1574296 bp 0x7ffe722807c0 sp 0x7ffe722807b8
+READ of size 4 at 0x602000000020 thread T0
+    #0 0x5636e1574295 in sum /tmp/san/m.c:3
+    #1 0x5636e1574456 in main /tmp/san/m.c:8
+    #2 0x7faddea45249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+
+0x602000000020 is located 0 bytes to the right of 16-byte region [0x602000000010,0x602000000020)
+allocated by thread T0 here:
+    #0 0x7faddf4b89cf in __interceptor_malloc ../../../../src/libsanitizer/asan/asan_malloc_linux.cpp:69
+    #1 0x5636e1574315 in main /tmp/san/m.c:5
+
+SUMMARY: AddressSanitizer: heap-buffer-overflow /tmp/san/m.c:3 in sum
+";
+        let reports = parse(stderr);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            reports[0].to_string(),
+            "heap-buffer-overflow at m.c:3 in sum"
+        );
+    }
+
+    #[test]
+    fn leak_skips_the_interceptor_frame() {
+        let stderr = "\
+=================================================================
+==23581==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 7 byte(s) in 1 object(s) allocated from:
+    #0 0x7f36da2b89cf in __interceptor_malloc ../../../../src/libsanitizer/asan/asan_malloc_linux.cpp:69
+    #1 0x562e025bb166 in make /tmp/san/l.c:2
+    #2 0x562e025bb171 in main /tmp/san/l.c:3
+    #3 0x7f36d9845249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+
+SUMMARY: AddressSanitizer: 7 byte(s) leaked in 1 allocation(s).
+";
+        let reports = parse(stderr);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].to_string(), "memory leak at l.c:2 in make");
+    }
+
+    #[test]
+    fn runtime_error_keeps_its_own_location() {
+        let stderr = "\
+m.c:7:40: runtime error: signed integer overflow: 2147483647 + 2 cannot be represented in type 'int'
+    #0 0x56447243443d in main /tmp/san/m.c:7
+    #1 0x7f1877c45249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+    #2 0x7f1877c45304 in __libc_start_main (/lib/x86_64-linux-gnu/libc.so.6+0x27304)
+";
+        let reports = parse(stderr);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            reports[0].to_string(),
+            "runtime error: signed integer overflow at m.c:7 in main"
+        );
+    }
+
+    #[test]
+    fn ignores_regular_stderr() {
+        assert!(parse("warning: input ended early\n#1 is not a frame\n").is_empty());
+    }
+}
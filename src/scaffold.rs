@@ -0,0 +1,42 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::config::{Config, CONFIG_FILE};
+
+const MAIN_SKELETON: &str = "\
+#include <stdio.h>
+#include <stdlib.h>
+
+int main(void)
+{
+    return EXIT_SUCCESS;
+}
+";
+
+/// Creates a project in `dir` with a source skeleton, empty test folders and, if `local_config`
+/// is set, an empty project-local `.cvutie`.
+pub fn new(config: &Config, dir: &Path, local_config: bool) -> Result<()> {
+    if dir.exists() {
+        bail!("`{}` already exists", dir.display());
+    }
+    let Some(source) = config.source_code_filenames.first() else {
+        bail!("`source_code_filenames` is empty, so there is no source file to create");
+    };
+
+    let write = |path: &Path, contents: &str| {
+        std::fs::write(path, contents)
+            .with_context(|| format!("Could not write `{}`", path.display()))
+    };
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Could not create `{}`", dir.display()))?;
+    write(&dir.join(source), MAIN_SKELETON)?;
+    for folder in &config.test_folder_names {
+        let path = dir.join(folder);
+        std::fs::create_dir(&path)
+            .with_context(|| format!("Could not create `{}`", path.display()))?;
+    }
+    if local_config {
+        write(&dir.join(CONFIG_FILE), "{}\n")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::{io::IsTerminal, sync::OnceLock};
+
+const RESET: &str = "\x1b[0m";
+
+fn enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED
+        .get_or_init(|| std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal())
+}
+
+fn paint(code: &str, text: &str) -> String {
+    if enabled() {
+        format!("{code}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn red(text: &str) -> String {
+    paint("\x1b[31m", text)
+}
+
+pub fn green(text: &str) -> String {
+    paint("\x1b[32m", text)
+}
+
+pub fn yellow(text: &str) -> String {
+    paint("\x1b[33m", text)
+}
+
+pub fn cyan(text: &str) -> String {
+    paint("\x1b[36m", text)
+}
+
+pub fn bold(text: &str) -> String {
+    paint("\x1b[1m", text)
+}
+
+/// Removes the escape codes added by the other helpers, e.g. before writing to a file.
+pub fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = rest.find('m').map_or("", |end| &rest[end + 1..]);
+    }
+    out.push_str(rest);
+    out
+}
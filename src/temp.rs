@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Tries `$TMPDIR/cvutie-{name}-{pid}-{n}{suffix}` with increasing `n` until `create` finds one
+/// that nothing is at yet.
+fn create<T>(
+    name: &str,
+    suffix: &str,
+    create: impl Fn(&Path) -> io::Result<T>,
+) -> Result<(PathBuf, T)> {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "cvutie-{name}-{}-{}{suffix}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        match create(&path) {
+            Ok(created) => return Ok((path, created)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Could not create `{}`", path.display()))
+            }
+        }
+    }
+}
+
+/// Creates an empty directory that no earlier run has left anything in.
+pub fn dir(name: &str) -> Result<PathBuf> {
+    Ok(create(name, "", |path| std::fs::create_dir(path))?.0)
+}
+
+/// Creates an empty file ending in `suffix`, never opening one that was already there.
+pub fn file(name: &str, suffix: &str) -> Result<(PathBuf, File)> {
+    create(name, suffix, |path| {
+        OpenOptions::new().write(true).create_new(true).open(path)
+    })
+}
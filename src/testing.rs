@@ -0,0 +1,529 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    checker::Checker,
+    compare,
+    compile::{self, Toolchain},
+    config::{self, config_path, Comparison, Config, PartialConfig},
+    diff,
+    execute::{self, Invocation, Limits, Run, Verdict},
+    hooks::{self, Hook},
+    memcheck, pool, region,
+    report::CaseReport,
+    sanitizer, style,
+};
+
+pub const INPUT_SUFFIX: &str = "_in.txt";
+pub const OUTPUT_SUFFIX: &str = "_out.txt";
+
+/// A single `NNNN_in.txt`/`NNNN_out.txt` pair.
+pub struct TestCase {
+    pub id: String,
+    pub input: PathBuf,
+    pub expected: PathBuf,
+}
+
+/// A test folder (e.g. `CZE`) and the cases found in it.
+pub struct Suite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+/// A directory containing at least one test folder.
+pub struct Project {
+    pub dir: PathBuf,
+    pub suites: Vec<Suite>,
+}
+
+pub enum Outcome {
+    Passed,
+    WrongOutput {
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// Killed by a signal or a limit before it could finish.
+    Crashed(Verdict),
+    /// A sanitizer reported errors, regardless of whether the output matched.
+    Sanitizer(Vec<sanitizer::Report>),
+    /// Memcheck found leaks or invalid accesses, regardless of whether the output matched.
+    Memcheck(memcheck::Summary),
+    /// The project's checker rejected the output, with its explanation.
+    Rejected(String),
+}
+
+/// Options of a `test-all` run.
+pub struct TestOptions {
+    /// Number of cases run concurrently.
+    pub jobs: usize,
+    /// Build with AddressSanitizer and UBSan and fail cases that trigger a report.
+    pub sanitize: bool,
+    /// Run every case under valgrind's memcheck and fail cases that leak or access invalid memory.
+    pub memcheck: bool,
+}
+
+#[derive(Default)]
+pub struct Tally {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Tally {
+    fn record(&mut self, passed: bool) {
+        if passed {
+            self.passed += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &Tally) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+    }
+
+    fn line(&self, label: &str) -> String {
+        let counts = format!("{}/{} passed", self.passed, self.passed + self.failed);
+        let counts = if self.failed == 0 {
+            style::green(&counts)
+        } else {
+            style::red(&counts)
+        };
+        format!("{label}: {counts}")
+    }
+}
+
+/// Finds every project below `root`, i.e. every directory with a child named in
+/// `Config::test_folder_names`.
+pub fn discover(config: &Config, root: &Path) -> Result<Vec<Project>> {
+    let mut projects = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let mut suites = Vec::new();
+        for entry in read_dir_sorted(&dir)? {
+            if !entry.is_dir() {
+                continue;
+            }
+            let name = entry
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            if config.test_folder_names.contains(&name) {
+                suites.push(Suite {
+                    cases: find_cases(&entry)?,
+                    name,
+                });
+            } else if !name.starts_with('.') {
+                pending.push(entry);
+            }
+        }
+        if !suites.is_empty() {
+            projects.push(Project { dir, suites });
+        }
+    }
+
+    projects.sort_by(|a, b| a.dir.cmp(&b.dir));
+    Ok(projects)
+}
+
+pub fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Could not read directory `{}`", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+fn find_cases(folder: &Path) -> Result<Vec<TestCase>> {
+    let mut cases = Vec::new();
+    for input in read_dir_sorted(folder)? {
+        let Some(id) = input
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(INPUT_SUFFIX))
+        else {
+            continue;
+        };
+
+        let expected = folder.join(format!("{id}{OUTPUT_SUFFIX}"));
+        if !expected.is_file() {
+            eprintln!(
+                "{} `{}` has no matching `{id}{OUTPUT_SUFFIX}`, skipping",
+                style::yellow("warning:"),
+                input.display()
+            );
+            continue;
+        }
+        cases.push(TestCase {
+            id: id.to_string(),
+            input: input.clone(),
+            expected,
+        });
+    }
+    Ok(cases)
+}
+
+/// The outcome of a case along with how the run itself ended.
+pub struct CaseRun {
+    pub outcome: Outcome,
+    pub verdict: Verdict,
+    pub duration: Duration,
+}
+
+/// How a project's outputs are judged.
+pub enum Judge {
+    Compare(Comparison),
+    Checker(Checker),
+}
+
+/// Feeds `case.input` to `binary` and judges its stdout against `case.expected`.
+pub fn run_case(
+    binary: &Path,
+    case: &TestCase,
+    judge: &Judge,
+    limits: &Limits,
+    options: &TestOptions,
+) -> Result<CaseRun> {
+    let input = File::open(&case.input)
+        .with_context(|| format!("Could not open `{}`", case.input.display()))?;
+    let mut invocation = Invocation::new(binary);
+    if options.sanitize {
+        invocation.env.extend(
+            sanitizer::ENV
+                .iter()
+                .map(|&(k, v)| (k.to_string(), v.to_string())),
+        );
+    }
+    let (output, summary) = if options.memcheck {
+        let (output, summary) = memcheck::run(binary, input.into(), true, limits)?;
+        (output, Some(summary))
+    } else {
+        (execute::run(&invocation, input.into(), true, limits)?, None)
+    };
+
+    let (verdict, duration) = (output.verdict, output.duration);
+    Ok(CaseRun {
+        outcome: outcome(case, judge, output, summary, options)?,
+        verdict,
+        duration,
+    })
+}
+
+fn outcome(
+    case: &TestCase,
+    judge: &Judge,
+    output: Run,
+    summary: Option<memcheck::Summary>,
+    options: &TestOptions,
+) -> Result<Outcome> {
+    if let Some(summary) = summary.filter(|summary| !summary.is_clean()) {
+        return Ok(Outcome::Memcheck(summary));
+    }
+    if options.sanitize {
+        let reports = sanitizer::parse(&String::from_utf8_lossy(&output.stderr));
+        if !reports.is_empty() {
+            return Ok(Outcome::Sanitizer(reports));
+        }
+    }
+    if !output.verdict.exited() {
+        return Ok(Outcome::Crashed(output.verdict));
+    }
+
+    if let Judge::Checker(checker) = judge {
+        let decision = checker.check(&case.input, &case.expected, &output.stdout)?;
+        return Ok(if decision.accepted {
+            Outcome::Passed
+        } else {
+            Outcome::Rejected(decision.message)
+        });
+    }
+
+    let expected = std::fs::read(&case.expected)
+        .with_context(|| format!("Could not read `{}`", case.expected.display()))?;
+    match judge {
+        Judge::Compare(comparison) if !compare::matches(comparison, &expected, &output.stdout) => {
+            Ok(Outcome::WrongOutput {
+                expected,
+                actual: output.stdout,
+            })
+        }
+        _ => Ok(Outcome::Passed),
+    }
+}
+
+/// A case queued for the worker pool.
+struct Job<'a> {
+    /// Index into the discovered projects.
+    project: usize,
+    suite: &'a str,
+    case: &'a TestCase,
+    binary: &'a Path,
+    judge: &'a Judge,
+}
+
+/// How to judge the project in `dir`: the `checker` from the closest `.cvutie` in `dir` or its
+/// ancestors, or from `home`, then the closest `comparison`, then the one for its region in
+/// `region_comparisons`, then the `comparison` from `home`. The `.cvutie` of the directory
+/// `test-all` runs from only counts for the projects inside it.
+fn judge_for(config: &Config, home: &Config, dir: &Path) -> Result<Judge> {
+    let (mut checker, mut comparison) = (home.checker.clone(), None);
+    for path in config::find_local_configs(&std::path::absolute(dir)?, config_path().as_deref()) {
+        let local = PartialConfig::load(&path)?;
+        checker = local.checker.or(checker);
+        comparison = local.comparison.or(comparison);
+    }
+
+    if let Some(command) = checker {
+        return Ok(Judge::Checker(Checker {
+            command,
+            dir: std::path::absolute(dir)?,
+        }));
+    }
+    if let Some(comparison) = comparison {
+        return Ok(Judge::Compare(comparison));
+    }
+    let region = region::containing(config, dir)
+        .and_then(|name| config.region_comparisons.as_ref()?.get(name));
+    Ok(Judge::Compare(region.copied().unwrap_or(home.comparison)))
+}
+
+/// Prints the outcome of a case and returns whether it passed.
+fn print_case(
+    label: &str,
+    outcome: Result<&Outcome, &anyhow::Error>,
+    options: &TestOptions,
+) -> bool {
+    match outcome {
+        Ok(Outcome::Passed) if options.memcheck => {
+            println!("  {label} {} (no leaks)", style::green("PASS"));
+            true
+        }
+        Ok(Outcome::Passed) => {
+            println!("  {label} {}", style::green("PASS"));
+            true
+        }
+        Ok(Outcome::WrongOutput { expected, actual }) => {
+            println!("  {label} {}", style::red("FAIL"));
+            let diff = diff::unified(
+                &String::from_utf8_lossy(expected),
+                &String::from_utf8_lossy(actual),
+            );
+            for line in diff.lines() {
+                println!("    {line}");
+            }
+            false
+        }
+        Ok(Outcome::Crashed(verdict)) => {
+            let tag = match verdict {
+                Verdict::WallTimeout(_) | Verdict::CpuTimeout(_) => "TIMEOUT",
+                _ => "CRASH",
+            };
+            println!("  {label} {} {verdict}", style::red(tag));
+            false
+        }
+        Ok(Outcome::Sanitizer(reports)) => {
+            println!("  {label} {}", style::red("SANITIZER"));
+            for report in reports {
+                println!("    {report}");
+            }
+            false
+        }
+        Ok(Outcome::Rejected(message)) => {
+            println!("  {label} {}", style::red("FAIL"));
+            for line in message.lines() {
+                println!("    {line}");
+            }
+            false
+        }
+        Ok(Outcome::Memcheck(summary)) => {
+            println!("  {label} {} {}", style::red("MEMCHECK"), summary.verdict());
+            for issue in &summary.issues {
+                println!("    {issue}");
+            }
+            false
+        }
+        Err(e) => {
+            println!("  {label} {} {e:#}", style::red("ERROR"));
+            false
+        }
+    }
+}
+
+/// What [`test_all`] found.
+pub struct Results {
+    pub total: Tally,
+    /// Every case in discovery order, including those of projects that failed to compile.
+    pub cases: Vec<CaseReport>,
+}
+
+/// Compiles and tests every project found below `roots`, printing a summary per project and in
+/// total. Cases run on `TestOptions::jobs` threads, but are reported in discovery order. `home` is
+/// `config` without the `.cvutie` files of the current directory.
+pub fn test_all(
+    config: &Config,
+    home: &Config,
+    roots: &[PathBuf],
+    options: &TestOptions,
+) -> Result<Results> {
+    let mut projects = Vec::new();
+    for root in roots {
+        projects.extend(discover(config, root)?);
+    }
+
+    if options.memcheck {
+        memcheck::ensure_available()?;
+    }
+    if projects.is_empty() {
+        println!(
+            "No test folders ({}) found",
+            config.test_folder_names.join(", ")
+        );
+    }
+
+    let mut toolchain = Toolchain::resolve(config, None)?;
+    let mut limits = Limits::from_config(config)?;
+    if options.sanitize {
+        toolchain
+            .args
+            .extend(sanitizer::FLAGS.iter().map(|&f| f.to_string()));
+        // AddressSanitizer reserves terabytes of address space up front.
+        limits.memory_mb = None;
+    }
+    if options.memcheck {
+        // Debug info gives memcheck's stack frames their file and line.
+        toolchain.args.push("-g".to_string());
+    }
+
+    // Keep the sanitized binary apart from the regular build.
+    let output = options
+        .sanitize
+        .then(|| format!("{}.sanitize", config.default_bin_output_name));
+    // Compile one project at a time so compiler diagnostics don't interleave.
+    let mut binaries = Vec::new();
+    for project in &projects {
+        hooks::run(config, Hook::PreCompile, &project.dir, &[])?;
+        let binary = compile::compile(config, &toolchain, &project.dir, output.as_deref());
+        if let Ok(binary) = &binary {
+            hooks::run_post_compile(config, &project.dir, binary);
+        }
+        binaries.push(binary);
+    }
+    for (project, _) in projects.iter().zip(&binaries).filter(|(_, b)| b.is_ok()) {
+        hooks::run(config, Hook::PreTest, &project.dir, &[])?;
+    }
+
+    let judges = projects
+        .iter()
+        .map(|project| judge_for(config, home, &project.dir))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut jobs = Vec::new();
+    for (index, (project, binary)) in projects.iter().zip(&binaries).enumerate() {
+        let Ok(binary) = binary else { continue };
+        let judge = &judges[index];
+        for suite in &project.suites {
+            jobs.extend(suite.cases.iter().map(|case| Job {
+                project: index,
+                suite: &suite.name,
+                case,
+                binary,
+                judge,
+            }));
+        }
+    }
+    let outcomes = pool::map(&jobs, options.jobs, |job| {
+        run_case(job.binary, job.case, job.judge, &limits, options)
+    });
+
+    let mut results = jobs.iter().zip(outcomes).peekable();
+    let mut tallies = Vec::new();
+    let mut cases = Vec::new();
+    for (index, (project, binary)) in projects.iter().zip(&binaries).enumerate() {
+        let mut tally = Tally::default();
+        println!("{}", style::bold(&project.dir.display().to_string()));
+        if let Err(e) = binary {
+            println!("  {} {e:#}", style::red("COMPILE ERROR"));
+            for suite in &project.suites {
+                tally.failed += suite.cases.len();
+                cases.extend(
+                    suite.cases.iter().map(|case| {
+                        CaseReport::compile_error(&project.dir, &suite.name, &case.id, e)
+                    }),
+                );
+            }
+        }
+        while let Some((job, run)) = results.next_if(|(job, _)| job.project == index) {
+            let label = format!("{}/{}", job.suite, job.case.id);
+            tally.record(print_case(
+                &label,
+                run.as_ref().map(|run| &run.outcome),
+                options,
+            ));
+            cases.push(CaseReport::new(&project.dir, job.suite, &job.case.id, &run));
+        }
+        tallies.push(tally);
+    }
+
+    let mut total = Tally::default();
+    println!();
+    for (project, tally) in projects.iter().zip(&tallies) {
+        println!("{}", tally.line(&project.dir.display().to_string()));
+        total.merge(tally);
+    }
+    println!("{}", style::bold(&total.line("Total")));
+
+    for (project, tally) in projects.iter().zip(&tallies) {
+        let env = [
+            ("CVUTIE_PASSED", tally.passed.to_string()),
+            ("CVUTIE_FAILED", tally.failed.to_string()),
+        ];
+        hooks::run_post(config, Hook::PostTest, &project.dir, &env);
+    }
+    Ok(Results { total, cases })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CONFIG_FILE;
+    use std::collections::HashMap;
+
+    #[test]
+    fn projects_of_a_region_keep_their_own_judges() {
+        let root = std::fs::canonicalize(crate::temp::dir("judge").unwrap()).unwrap();
+        let projects = ["p1", "p2", "p3"].map(|name| root.join(name));
+        for dir in &projects {
+            std::fs::create_dir(dir).unwrap();
+        }
+        let local = r#"{"checker": "./chk.sh", "comparison": "ignore_whitespace"}"#;
+        std::fs::write(projects[0].join(CONFIG_FILE), local).unwrap();
+        let local = r#"{"comparison": "unordered_lines"}"#;
+        std::fs::write(projects[1].join(CONFIG_FILE), local).unwrap();
+
+        let home = Config {
+            regions: Some(HashMap::from([("hw".to_string(), vec![root.clone()])])),
+            ..Config::default()
+        };
+        // What `test-all hw` sees when run from inside `p1`.
+        let mut config = home.clone();
+        config.merge(PartialConfig::load(projects[0].join(CONFIG_FILE)).unwrap());
+
+        let judges = projects
+            .each_ref()
+            .map(|dir| judge_for(&config, &home, dir));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(matches!(&judges[0], Ok(Judge::Checker(checker)) if checker.command == "./chk.sh"));
+        assert!(matches!(
+            judges[1],
+            Ok(Judge::Compare(Comparison::UnorderedLines))
+        ));
+        assert!(matches!(judges[2], Ok(Judge::Compare(Comparison::Exact))));
+    }
+}